@@ -1,9 +1,9 @@
 use bnf::*;
 use criterion::measurement::WallTime;
 use criterion::{
-    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion,
-    PlottingBackend,
+    criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, PlottingBackend,
 };
+use std::hint::black_box;
 use std::time::Duration;
 
 fn bench_generator(group: &mut BenchmarkGroup<WallTime>, input: (Grammar, GenerationStrategy)) {
@@ -29,6 +29,22 @@ fn bench_generator(group: &mut BenchmarkGroup<WallTime>, input: (Grammar, Genera
     );
 }
 
+fn bench_parser(
+    group: &mut BenchmarkGroup<WallTime>,
+    input: (Grammar, Vec<String>),
+    backend: ParserBackend,
+    name: &str,
+) {
+    let mut parser = input.0.parser(backend).unwrap();
+    group.bench_with_input(BenchmarkId::new("parse", name), &input, |b, input| {
+        b.iter(|| {
+            for word in input.1.iter() {
+                parser.parse(black_box(word)).unwrap();
+            }
+        });
+    });
+}
+
 fn bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("generator_benches");
     bench_generator(
@@ -39,6 +55,22 @@ fn bench(c: &mut Criterion) {
         ),
     );
     group.finish();
+    let grammar = bnf::corpus::grammar_bnf();
+    let words = (0..20)
+        .map(|seed| {
+            grammar
+                .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+                .unwrap()
+        })
+        .collect::<Vec<String>>();
+    let mut group = c.benchmark_group("parser_benches");
+    for (backend, name) in [
+        (ParserBackend::Earley, "bnf_earley"),
+        (ParserBackend::GLL, "bnf_gll"),
+    ] {
+        bench_parser(&mut group, (grammar.clone(), words.clone()), backend, name);
+    }
+    group.finish();
 }
 
 criterion_group! {
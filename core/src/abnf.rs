@@ -0,0 +1,438 @@
+use crate::alternatives::Alternatives;
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::reader::Reader;
+use crate::rule::Rule;
+use crate::term::Term;
+use crate::types::FxHashMap;
+use crate::types::NoHashSet;
+use std::rc::Rc;
+
+// RFC 5234, appendix B.1
+const CORE_RULES: &str = r#"
+ALPHA   = %x41-5A / %x61-7A
+BIT     = "0" / "1"
+CHAR    = %x01-7F
+CR      = %x0D
+CRLF    = CR LF
+CTL     = %x00-1F / %x7F
+DIGIT   = %x30-39
+DQUOTE  = %x22
+HEXDIG  = DIGIT / "A" / "B" / "C" / "D" / "E" / "F"
+HTAB    = %x09
+LF      = %x0A
+LWSP    = *(WSP / CRLF WSP)
+OCTET   = %x00-FF
+SP      = %x20
+VCHAR   = %x21-7E
+WSP     = SP / HTAB
+"#;
+
+const MAX_VALUE_RANGE: u32 = 1 << 16;
+// repetitions are unrolled, so their counts are bounded like the value ranges
+const MAX_REPETITION_COUNT: usize = 1 << 10;
+
+#[derive(Default)]
+struct ABNFReader {
+    names: FxHashMap<String, String>,
+    letters: FxHashMap<char, Term>,
+    current: String,
+    n_helpers: usize,
+}
+
+pub(crate) fn parse(text: &str) -> Result<Grammar, Error> {
+    let mut abnf = ABNFReader::default();
+    let (mut rules, mut helpers) = abnf.rulelist(text)?;
+    // core rules are implicitly available, they are added wherever the grammar references one
+    // without defining it
+    let (core, core_helpers) = abnf.rulelist(CORE_RULES)?;
+    let mut defined = (rules.iter())
+        .chain(helpers.iter())
+        .map(|rule| rule.lhs.key)
+        .collect::<NoHashSet<_>>();
+    let mut pending = (rules.iter())
+        .chain(helpers.iter())
+        .flat_map(|rule| rule.rhs.alternatives.iter())
+        .flat_map(|terms| terms.iter())
+        .filter(|term| term.is_nonterminal())
+        .map(|term| term.key)
+        .collect::<Vec<_>>();
+    while let Some(key) = pending.pop() {
+        if !defined.insert(key) {
+            continue;
+        }
+        let core_rules = core.iter().map(|rule| (rule, false));
+        let core_helpers = core_helpers.iter().map(|rule| (rule, true));
+        for (rule, helper) in core_rules
+            .chain(core_helpers)
+            .filter(|(rule, _)| rule.lhs.key == key)
+        {
+            pending.extend(
+                rule.rhs
+                    .alternatives
+                    .iter()
+                    .flat_map(|terms| terms.iter())
+                    .filter(|term| term.is_nonterminal())
+                    .map(|term| term.key),
+            );
+            match helper {
+                true => helpers.push(rule.clone()),
+                false => rules.push(rule.clone()),
+            }
+        }
+    }
+    Grammar::builder()
+        .rules(&rules)
+        .helpers(&helpers)
+        .try_build()
+}
+
+impl ABNFReader {
+    fn rulelist(&mut self, text: &str) -> Result<(Vec<Rule>, Vec<Rule>), Error> {
+        let mut reader = Reader::new(text);
+        let mut rules = Vec::new();
+        let mut helpers = Vec::new();
+        skip_empty_lines(&mut reader);
+        while !reader.is_done() {
+            rules.push(self.rule(&mut reader, &mut helpers)?);
+            skip_empty_lines(&mut reader);
+        }
+        Ok((rules, helpers))
+    }
+
+    fn rule(&mut self, reader: &mut Reader, helpers: &mut Vec<Rule>) -> Result<Rule, Error> {
+        let lhs = self.rulename(reader)?;
+        self.current = lhs.content.clone();
+        skip_c_wsp(reader);
+        if !reader.eat_str("=/") && !reader.eat('=') {
+            return Err(reader.unexpected("'=' or '=/'"));
+        }
+        skip_c_wsp(reader);
+        let mut weights = Vec::new();
+        let rhs = self.alternation(reader, helpers, Some(&mut weights))?;
+        skip_c_wsp(reader);
+        if !reader.is_done() && !matches!(reader.peek(), Some('\r' | '\n' | ';')) {
+            return Err(reader.unexpected("end of line"));
+        }
+        Ok(Rule {
+            lhs: Rc::new(lhs),
+            rhs: Alternatives::try_from(rhs.into_iter().zip(weights).collect::<Vec<_>>())?,
+        })
+    }
+
+    fn rulename(&mut self, reader: &mut Reader) -> Result<Term, Error> {
+        if !reader.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            return Err(reader.unexpected("a rule name"));
+        }
+        let name = reader.take_while(|c| c.is_ascii_alphanumeric() || c == '-');
+        // rule names are case insensitive, the first spelling encountered is used throughout
+        let name = self.names.entry(name.to_ascii_lowercase()).or_insert(name);
+        Ok(Term::nonterminal(name))
+    }
+
+    fn alternation(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+        mut weights: Option<&mut Vec<f64>>,
+    ) -> Result<Vec<Vec<Term>>, Error> {
+        // only the alternatives of a rule itself can be weighted, not those of groups or options
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(self.concatenation(reader, helpers)?);
+            if let Some(weights) = weights.as_deref_mut() {
+                let mark = reader.mark();
+                skip_c_wsp(reader);
+                if reader.peek() != Some('@') {
+                    reader.reset(mark);
+                }
+                weights.push(reader.weight()?);
+            }
+            let mark = reader.mark();
+            skip_c_wsp(reader);
+            if !reader.eat('/') {
+                reader.reset(mark);
+                return Ok(alternatives);
+            }
+            skip_c_wsp(reader);
+        }
+    }
+
+    fn concatenation(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+    ) -> Result<Vec<Term>, Error> {
+        let mut terms = self.repetition(reader, helpers)?;
+        loop {
+            let mark = reader.mark();
+            skip_c_wsp(reader);
+            let separated = reader.mark().position() > mark.position();
+            if !separated
+                || !reader.peek().is_some_and(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '*' | '(' | '[' | '"' | '%' | '<')
+                })
+            {
+                reader.reset(mark);
+                return Ok(terms);
+            }
+            terms.append(&mut self.repetition(reader, helpers)?);
+        }
+    }
+
+    fn repetition(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+    ) -> Result<Vec<Term>, Error> {
+        let start = reader.mark();
+        let min = reader.take_while(|c| c.is_ascii_digit());
+        let max = match reader.eat('*') {
+            true => reader.take_while(|c| c.is_ascii_digit()),
+            false => min.clone(),
+        };
+        let count = |digits: &str, default: Option<usize>| match digits.is_empty() {
+            true => Ok(default),
+            false => (digits.parse::<usize>().ok())
+                .filter(|&count| count <= MAX_REPETITION_COUNT)
+                .map(Some)
+                .ok_or_else(|| reader.error_at(start, "repetition count is too large")),
+        };
+        let (min, max) = match (min.is_empty(), reader.mark().position() > start.position()) {
+            (true, false) => (1, Some(1)),
+            _ => (count(&min, Some(0))?.unwrap_or(0), count(&max, None)?),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(reader.error_at(start, "repetition maximum is less than its minimum"));
+        }
+        let element = self.element(reader, helpers)?;
+        let element = self.splice(element, helpers);
+        let mut terms = element
+            .iter()
+            .cycle()
+            .take(element.len() * min)
+            .cloned()
+            .collect::<Vec<Term>>();
+        match max {
+            None => {
+                let helper = self.helper("rep");
+                let mut repetition = element.clone();
+                repetition.push(helper.clone());
+                helpers.push(helper_rule(
+                    &helper,
+                    vec![vec![Term::terminal("")], repetition],
+                ));
+                terms.push(helper);
+            }
+            Some(max) => {
+                // bounded repetitions become a chain of nested options
+                let mut tail: Option<Term> = None;
+                for _ in min..max {
+                    let helper = self.helper("opt");
+                    let mut option = element.clone();
+                    option.extend(tail);
+                    helpers.push(helper_rule(&helper, vec![option, vec![Term::terminal("")]]));
+                    tail = Some(helper);
+                }
+                terms.extend(tail);
+            }
+        }
+        if terms.is_empty() {
+            terms.push(Term::terminal(""));
+        }
+        Ok(terms)
+    }
+
+    fn element(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+    ) -> Result<Vec<Vec<Term>>, Error> {
+        let start = reader.mark();
+        match reader.peek() {
+            Some('(') => {
+                reader.next();
+                skip_c_wsp(reader);
+                let alternatives = self.alternation(reader, helpers, None)?;
+                skip_c_wsp(reader);
+                reader.expect_str(")")?;
+                Ok(alternatives)
+            }
+            Some('[') => {
+                reader.next();
+                skip_c_wsp(reader);
+                let mut alternatives = self.alternation(reader, helpers, None)?;
+                skip_c_wsp(reader);
+                reader.expect_str("]")?;
+                alternatives.push(vec![Term::terminal("")]);
+                let helper = self.helper("opt");
+                helpers.push(helper_rule(&helper, alternatives));
+                Ok(vec![vec![helper]])
+            }
+            Some('"') => self.char_val(reader, helpers, false),
+            Some('%') if reader.eat_str("%s") => self.char_val(reader, helpers, true),
+            Some('%') if reader.eat_str("%i") => self.char_val(reader, helpers, false),
+            Some('%') => {
+                reader.next();
+                let radix = match reader.next().map(|c| c.to_ascii_lowercase()) {
+                    Some('b') => 2,
+                    Some('d') => 10,
+                    Some('x') => 16,
+                    _ => return Err(reader.error_at(start, "expected 'b', 'd' or 'x' after '%'")),
+                };
+                self.num_val(reader, helpers, radix)
+            }
+            Some('<') => {
+                // prose values describe their strings informally and cannot be recognized
+                reader.next();
+                let prose = reader.take_while(|c| c != '>');
+                if !reader.eat('>') {
+                    return Err(reader.error_at(start, "unterminated prose value"));
+                }
+                Err(reader
+                    .unsupported_at(start, &format!("prose value <{}> is not supported", prose)))
+            }
+            Some(c) if c.is_ascii_alphabetic() => Ok(vec![vec![self.rulename(reader)?]]),
+            _ => Err(reader.unexpected("an element")),
+        }
+    }
+
+    fn char_val(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+        case_sensitive: bool,
+    ) -> Result<Vec<Vec<Term>>, Error> {
+        let start = reader.mark();
+        if !reader.eat('"') {
+            return Err(reader.unexpected("'\"'"));
+        }
+        let content = reader.take_while(|c| matches!(c, ' '..='~') && c != '"');
+        if !reader.eat('"') {
+            return Err(reader.error_at(start, "unterminated string literal"));
+        }
+        if case_sensitive || content.is_empty() {
+            return Ok(vec![vec![Term::terminal(&content)]]);
+        }
+        // letters of case insensitive strings match either case through a shared helper each
+        let mut terms = Vec::new();
+        let mut literal = String::new();
+        for c in content.chars() {
+            if !c.is_ascii_alphabetic() {
+                literal.push(c);
+                continue;
+            }
+            if !literal.is_empty() {
+                terms.push(Term::terminal(&std::mem::take(&mut literal)));
+            }
+            let c = c.to_ascii_lowercase();
+            if !self.letters.contains_key(&c) {
+                let helper = self.helper("group");
+                helpers.push(helper_rule(
+                    &helper,
+                    vec![
+                        vec![Term::terminal(&c.to_string())],
+                        vec![Term::terminal(&c.to_ascii_uppercase().to_string())],
+                    ],
+                ));
+                self.letters.insert(c, helper);
+            }
+            terms.push(self.letters[&c].clone());
+        }
+        if !literal.is_empty() {
+            terms.push(Term::terminal(&literal));
+        }
+        Ok(vec![terms])
+    }
+
+    fn num_val(
+        &mut self,
+        reader: &mut Reader,
+        helpers: &mut Vec<Rule>,
+        radix: u32,
+    ) -> Result<Vec<Vec<Term>>, Error> {
+        let first = value(reader, radix)?;
+        if reader.eat('-') {
+            let last = value(reader, radix)?;
+            let (first, last) = (u32::from(first), u32::from(last));
+            if last < first || last - first >= MAX_VALUE_RANGE {
+                return Err(reader.error("value range is empty or too large"));
+            }
+            let alternatives = (first..=last)
+                .filter_map(char::from_u32)
+                .map(|c| vec![Term::terminal(&c.to_string())])
+                .collect::<Vec<Vec<Term>>>();
+            let helper = self.helper("group");
+            helpers.push(helper_rule(&helper, alternatives));
+            return Ok(vec![vec![helper]]);
+        }
+        let mut content = first.to_string();
+        while reader.eat('.') {
+            content.push(value(reader, radix)?);
+        }
+        Ok(vec![vec![Term::terminal(&content)]])
+    }
+
+    fn splice(&mut self, mut alternatives: Vec<Vec<Term>>, helpers: &mut Vec<Rule>) -> Vec<Term> {
+        match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => {
+                let helper = self.helper("group");
+                helpers.push(helper_rule(&helper, alternatives));
+                vec![helper]
+            }
+        }
+    }
+
+    fn helper(&mut self, kind: &str) -> Term {
+        self.n_helpers += 1;
+        Term::nonterminal(&format!("{}.{}{}", self.current, kind, self.n_helpers))
+    }
+}
+
+fn helper_rule(lhs: &Term, alternatives: Vec<Vec<Term>>) -> Rule {
+    Rule {
+        lhs: Rc::new(lhs.clone()),
+        rhs: Alternatives::from(alternatives),
+    }
+}
+
+fn value(reader: &mut Reader, radix: u32) -> Result<char, Error> {
+    let start = reader.mark();
+    let digits = reader.take_while(|c| c.is_digit(radix));
+    u32::from_str_radix(&digits, radix)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| match digits.is_empty() {
+            true => reader.unexpected("a numeric value"),
+            false => reader.error_at(start, "invalid character value"),
+        })
+}
+
+fn skip_c_wsp(reader: &mut Reader) {
+    // whitespace may continue onto the next line as long as that line is indented
+    loop {
+        reader.take_while(|c| c == ' ' || c == '\t');
+        if reader.peek() == Some(';') {
+            reader.take_while(|c| c != '\r' && c != '\n');
+        }
+        let mark = reader.mark();
+        if !reader.eat_str("\r\n") && !reader.eat('\n') {
+            return;
+        }
+        if !matches!(reader.peek(), Some(' ' | '\t')) {
+            reader.reset(mark);
+            return;
+        }
+    }
+}
+
+fn skip_empty_lines(reader: &mut Reader) {
+    loop {
+        reader.skip_whitespace();
+        if reader.peek() != Some(';') {
+            return;
+        }
+        reader.take_while(|c| c != '\n');
+    }
+}
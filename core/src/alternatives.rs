@@ -1,4 +1,5 @@
 use crate::codify::Codify;
+use crate::error::Error;
 use crate::term::Term;
 use itertools::Itertools;
 use std::fmt;
@@ -7,29 +8,71 @@ use std::rc::Rc;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Alternatives {
     pub(crate) alternatives: Vec<Rc<Vec<Term>>>,
+    // the relative weight of each alternative when generating
+    pub(crate) weights: Vec<f64>,
 }
 
 impl Alternatives {
     pub fn new() -> Self {
         Self {
             alternatives: Vec::new(),
+            weights: Vec::new(),
         }
     }
 
     pub fn add_alternative(&mut self, alternative: Vec<Term>) {
         self.alternatives.push(Rc::new(alternative));
+        self.weights.push(1.0);
+    }
+
+    pub fn add_weighted_alternative(
+        &mut self,
+        alternative: Vec<Term>,
+        weight: f64,
+    ) -> Result<(), Error> {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(Error::InvalidWeightError(format!(
+                "weight {} of an alternative is not finite and non-negative",
+                weight
+            )));
+        }
+        self.alternatives.push(Rc::new(alternative));
+        self.weights.push(weight);
+        Ok(())
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
     }
 
     pub fn merge(&self, other: &Self) -> Self {
-        return Self {
-            alternatives: self
-                .alternatives
-                .iter()
-                .chain(other.alternatives.iter())
-                .cloned()
-                .unique()
-                .collect(),
-        };
+        // an alternative occurring in both keeps the weight it has in the first
+        let (alternatives, weights) = self
+            .alternatives
+            .iter()
+            .zip(self.weights.iter())
+            .chain(other.alternatives.iter().zip(other.weights.iter()))
+            .unique_by(|(terms, _)| *terms)
+            .map(|(terms, weight)| (terms.clone(), *weight))
+            .unzip();
+        Self {
+            alternatives,
+            weights,
+        }
+    }
+
+    pub(crate) fn filter(&self, predicate: impl Fn(&[Term]) -> bool) -> Self {
+        let (alternatives, weights) = self
+            .alternatives
+            .iter()
+            .zip(self.weights.iter())
+            .filter(|(terms, _)| predicate(terms))
+            .map(|(terms, weight)| (terms.clone(), *weight))
+            .unzip();
+        Self {
+            alternatives,
+            weights,
+        }
     }
 }
 
@@ -42,11 +85,24 @@ impl Default for Alternatives {
 impl From<Vec<Vec<Term>>> for Alternatives {
     fn from(alternatives: Vec<Vec<Term>>) -> Self {
         Self {
+            weights: vec![1.0; alternatives.len()],
             alternatives: alternatives.into_iter().map(Rc::new).collect(),
         }
     }
 }
 
+impl TryFrom<Vec<(Vec<Term>, f64)>> for Alternatives {
+    type Error = Error;
+
+    fn try_from(alternatives: Vec<(Vec<Term>, f64)>) -> Result<Self, Self::Error> {
+        let mut result = Self::new();
+        for (alternative, weight) in alternatives {
+            result.add_weighted_alternative(alternative, weight)?;
+        }
+        Ok(result)
+    }
+}
+
 impl fmt::Display for Alternatives {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -54,28 +110,40 @@ impl fmt::Display for Alternatives {
             "{}",
             self.alternatives
                 .iter()
-                .map(|alternative| alternative
-                    .iter()
-                    .map(|term| term.to_string())
-                    .intersperse(" ".into())
-                    .collect::<String>())
-                .intersperse(" | ".into())
-                .collect::<String>()
+                .zip(self.weights.iter())
+                .map(|(alternative, weight)| {
+                    let terms = alternative.iter().map(|term| term.to_string()).join(" ");
+                    match *weight == 1.0 {
+                        true => terms,
+                        false => format!("{} @ {}", terms, weight),
+                    }
+                })
+                .join(" | ")
         )
     }
 }
 
 impl Codify for Alternatives {
     fn codify(&self, prefix: &str) -> String {
-        let mut s = format!("{prefix}Alternatives::from(vec![\n");
-        for alternative in self.alternatives.iter() {
-            s.push_str("                vec![");
+        // weights are only spelled out if any of them differs from the default, they were
+        // validated when these alternatives were built
+        let is_weighted = self.weights.iter().any(|weight| *weight != 1.0);
+        let (constructor, open, close) = match is_weighted {
+            true => ("try_from", "(", ").unwrap()"),
+            false => ("from", "", ")"),
+        };
+        let mut s = format!("{prefix}Alternatives::{constructor}(vec![\n");
+        for (alternative, weight) in self.alternatives.iter().zip(self.weights.iter()) {
+            s.push_str(&format!("                {open}vec!["));
             for term in alternative.iter() {
                 s.push_str(&format!("{},", term.codify(prefix)));
             }
-            s.push_str("],\n");
+            match is_weighted {
+                true => s.push_str(&format!("], {:?}),\n", weight)),
+                false => s.push_str("],\n"),
+            }
         }
-        s.push_str("            ])");
+        s.push_str(&format!("            ]{close}"));
         s
     }
 }
@@ -0,0 +1,446 @@
+use crate::grammar::Grammar;
+use crate::term::Term;
+use crate::types::NoHashMap;
+use crate::types::NoHashSet;
+use crate::types::TermKey;
+use itertools::Itertools;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::fmt;
+
+pub struct GrammarAnalysis<'a> {
+    grammar: &'a Grammar,
+    k: usize,
+    nullable: NoHashSet<TermKey>,
+    first: NoHashMap<TermKey, BTreeSet<String>>,
+    follow: NoHashMap<TermKey, BTreeSet<String>>,
+}
+
+impl<'a> GrammarAnalysis<'a> {
+    pub(crate) fn new(grammar: &'a Grammar, k: usize) -> Self {
+        let mut analysis = Self {
+            grammar,
+            k,
+            nullable: nullable_nonterminals(grammar),
+            first: Default::default(),
+            follow: Default::default(),
+        };
+        analysis.compute_first();
+        analysis.compute_follow();
+        analysis
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn nullable(&self) -> Vec<&'a Term> {
+        self.grammar
+            .rules
+            .iter()
+            .map(|rule| rule.lhs.as_ref())
+            .filter(|lhs| self.nullable.contains(&lhs.key))
+            .collect()
+    }
+
+    pub fn is_nullable(&self, term: &Term) -> bool {
+        term.is_epsilon() || self.nullable.contains(&term.key)
+    }
+
+    // lookahead strings shorter than k characters end where the derived string ends, in FOLLOW
+    // sets this means the end of the input
+    pub fn first(&self, term: &Term) -> BTreeSet<String> {
+        match term.is_terminal() {
+            true => BTreeSet::from([term.content.chars().take(self.k).collect()]),
+            false => self.first.get(&term.key).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn first_of(&self, terms: &[Term]) -> BTreeSet<String> {
+        terms
+            .iter()
+            .fold(BTreeSet::from([String::new()]), |acc, term| {
+                concatenate(&acc, &self.first(term), self.k)
+            })
+    }
+
+    pub fn follow(&self, term: &Term) -> BTreeSet<String> {
+        self.follow.get(&term.key).cloned().unwrap_or_default()
+    }
+
+    // the predict set of an alternative is its FIRST set, extended by the FOLLOW set of the rule if
+    // the alternative is nullable, two alternatives predicted by the same terminal conflict
+    pub fn ll1_conflicts(&self) -> Vec<LL1Conflict<'a>> {
+        let mut conflicts = Vec::new();
+        for rule in self.grammar.rules.iter() {
+            let follow = self.follow(&rule.lhs);
+            let alternatives = rule
+                .rhs
+                .alternatives
+                .iter()
+                .map(|terms| {
+                    let mut first = self.first_of(terms);
+                    let nullable = first.remove("");
+                    let predict = match nullable {
+                        true => first.union(&follow).cloned().collect(),
+                        false => first.clone(),
+                    };
+                    (terms.as_slice(), first, predict)
+                })
+                .collect::<Vec<_>>();
+            for [i, j] in (0..alternatives.len()).array_combinations() {
+                let (left, left_first, left_predict) = &alternatives[i];
+                let (right, right_first, right_predict) = &alternatives[j];
+                let first = left_first
+                    .intersection(right_first)
+                    .cloned()
+                    .collect::<BTreeSet<String>>();
+                let follow = left_predict
+                    .intersection(right_predict)
+                    .filter(|terminal| !first.contains(*terminal))
+                    .cloned()
+                    .collect::<BTreeSet<String>>();
+                let kinds = [
+                    (LL1ConflictKind::FirstFirst, first),
+                    (LL1ConflictKind::FirstFollow, follow),
+                ];
+                for (kind, terminals) in kinds {
+                    if terminals.is_empty() {
+                        continue;
+                    }
+                    conflicts.push(LL1Conflict {
+                        nonterminal: rule.lhs.as_ref(),
+                        kind,
+                        alternatives: [(i, *left), (j, *right)],
+                        terminals,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn compute_first(&mut self) {
+        let mut was_updated = true;
+        while was_updated {
+            was_updated = false;
+            for rule in self.grammar.rules.iter() {
+                let mut first = self.first.get(&rule.lhs.key).cloned().unwrap_or_default();
+                let size = first.len();
+                for terms in rule.rhs.alternatives.iter() {
+                    first.extend(self.first_of(terms));
+                }
+                if first.len() > size {
+                    self.first.insert(rule.lhs.key, first);
+                    was_updated = true;
+                }
+            }
+        }
+    }
+
+    fn compute_follow(&mut self) {
+        self.follow
+            .insert(self.grammar.start, BTreeSet::from([String::new()]));
+        let mut was_updated = true;
+        while was_updated {
+            was_updated = false;
+            for rule in self.grammar.rules.iter() {
+                let lhs_follow = self.follow(&rule.lhs);
+                for terms in rule.rhs.alternatives.iter() {
+                    for (i, term) in terms.iter().enumerate() {
+                        if !term.is_nonterminal() {
+                            continue;
+                        }
+                        let candidates =
+                            concatenate(&self.first_of(&terms[i + 1..]), &lhs_follow, self.k);
+                        let follow = self.follow.entry(term.key).or_default();
+                        let size = follow.len();
+                        follow.extend(candidates);
+                        was_updated |= follow.len() > size;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LL1ConflictKind {
+    FirstFirst,
+    FirstFollow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LL1Conflict<'a> {
+    pub(crate) nonterminal: &'a Term,
+    pub(crate) kind: LL1ConflictKind,
+    pub(crate) alternatives: [(usize, &'a [Term]); 2],
+    pub(crate) terminals: BTreeSet<String>,
+}
+
+impl<'a> LL1Conflict<'a> {
+    pub fn nonterminal(&self) -> &'a Term {
+        self.nonterminal
+    }
+
+    pub fn kind(&self) -> LL1ConflictKind {
+        self.kind
+    }
+
+    // each alternative comes with its index in the rule of the nonterminal
+    pub fn alternatives(&self) -> [(usize, &'a [Term]); 2] {
+        self.alternatives
+    }
+
+    // the empty string stands for the end of the input
+    pub fn terminals(&self) -> &BTreeSet<String> {
+        &self.terminals
+    }
+}
+
+impl fmt::Display for LL1Conflict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            LL1ConflictKind::FirstFirst => "FIRST/FIRST",
+            LL1ConflictKind::FirstFollow => "FIRST/FOLLOW",
+        };
+        let [left, right] = self.alternatives.map(|(_, terms)| match terms.is_empty() {
+            true => "\"\"".to_owned(),
+            false => terms.iter().join(" "),
+        });
+        let mut terminals = self
+            .terminals
+            .iter()
+            .map(|terminal| match terminal.is_empty() {
+                true => "end of input".to_owned(),
+                false => format!("\"{}\"", terminal.escape_default()),
+            });
+        write!(
+            f,
+            "{} conflict for {} between {} and {} on {}",
+            kind,
+            self.nonterminal,
+            left,
+            right,
+            terminals.join(", ")
+        )
+    }
+}
+
+fn concatenate(
+    prefixes: &BTreeSet<String>,
+    suffixes: &BTreeSet<String>,
+    k: usize,
+) -> BTreeSet<String> {
+    let mut result = BTreeSet::new();
+    for prefix in prefixes.iter() {
+        let length = prefix.chars().count();
+        if length >= k {
+            result.insert(prefix.clone());
+            continue;
+        }
+        for suffix in suffixes.iter() {
+            result.insert(prefix.chars().chain(suffix.chars()).take(k).collect());
+        }
+    }
+    result
+}
+
+pub(crate) fn nullable_nonterminals(grammar: &Grammar) -> NoHashSet<TermKey> {
+    let mut nullable = NoHashSet::default();
+    let mut was_updated = true;
+    while was_updated {
+        was_updated = false;
+        for rule in grammar.rules.iter() {
+            if !nullable.contains(&rule.lhs.key)
+                && rule.rhs.alternatives.iter().any(|terms| {
+                    terms
+                        .iter()
+                        .all(|term| term.is_epsilon() || nullable.contains(&term.key))
+                })
+            {
+                nullable.insert(rule.lhs.key);
+                was_updated = true;
+            }
+        }
+    }
+    nullable
+}
+
+pub(crate) fn productive_nonterminals(grammar: &Grammar) -> NoHashSet<TermKey> {
+    let mut productive = NoHashSet::default();
+    let mut was_updated = true;
+    while was_updated {
+        was_updated = false;
+        for rule in grammar.rules.iter() {
+            if !productive.contains(&rule.lhs.key)
+                && rule.rhs.alternatives.iter().any(|terms| {
+                    terms
+                        .iter()
+                        .all(|term| term.is_terminal() || productive.contains(&term.key))
+                })
+            {
+                productive.insert(rule.lhs.key);
+                was_updated = true;
+            }
+        }
+    }
+    productive
+}
+
+pub(crate) fn reachable_nonterminals(grammar: &Grammar) -> NoHashSet<TermKey> {
+    let mut reachable = NoHashSet::from_iter([grammar.start]);
+    let mut pending = vec![grammar.start];
+    while let Some(key) = pending.pop() {
+        let terms = grammar
+            .rule(key)
+            .into_iter()
+            .flat_map(|rule| rule.rhs.alternatives.iter())
+            .flat_map(|terms| terms.iter());
+        for term in terms {
+            if term.is_nonterminal() && reachable.insert(term.key) {
+                pending.push(term.key);
+            }
+        }
+    }
+    reachable
+}
+
+pub(crate) fn left_corners(grammar: &Grammar) -> NoHashMap<TermKey, Vec<TermKey>> {
+    // a nonterminal is a left corner of a rule if everything in front of it may derive epsilon
+    let nullable = nullable_nonterminals(grammar);
+    let mut left_corners = NoHashMap::<TermKey, Vec<TermKey>>::default();
+    for rule in grammar.rules.iter() {
+        let corners = left_corners.entry(rule.lhs.key).or_default();
+        for terms in rule.rhs.alternatives.iter() {
+            for term in terms.iter() {
+                if term.is_nonterminal() && !corners.contains(&term.key) {
+                    corners.push(term.key);
+                }
+                if !term.is_epsilon() && !nullable.contains(&term.key) {
+                    break;
+                }
+            }
+        }
+    }
+    left_corners
+}
+
+pub(crate) fn left_recursive_components(grammar: &Grammar) -> Vec<Vec<TermKey>> {
+    let left_corners = left_corners(grammar);
+    let reachable = |from: TermKey| {
+        let mut reachable = NoHashSet::<TermKey>::default();
+        let mut pending = vec![from];
+        while let Some(key) = pending.pop() {
+            for &corner in left_corners.get(&key).into_iter().flatten() {
+                if reachable.insert(corner) {
+                    pending.push(corner);
+                }
+            }
+        }
+        reachable
+    };
+    let closure = grammar
+        .rules
+        .iter()
+        .map(|rule| (rule.lhs.key, reachable(rule.lhs.key)))
+        .collect::<NoHashMap<TermKey, NoHashSet<TermKey>>>();
+    let mut assigned = NoHashSet::<TermKey>::default();
+    let mut components = Vec::new();
+    for rule in grammar.rules.iter() {
+        let key = rule.lhs.key;
+        if assigned.contains(&key) || !closure[&key].contains(&key) {
+            continue;
+        }
+        let component = grammar
+            .rules
+            .iter()
+            .map(|rule| rule.lhs.key)
+            .filter(|other| closure[&key].contains(other) && closure[other].contains(&key))
+            .collect::<Vec<TermKey>>();
+        assigned.extend(component.iter().copied());
+        components.push(component);
+    }
+    components
+}
+
+pub(crate) fn left_recursion_cycles(grammar: &Grammar) -> Vec<Vec<TermKey>> {
+    // one shortest cycle per left recursive nonterminal, rotated to start at its earliest rule
+    let left_corners = left_corners(grammar);
+    let order = grammar
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| (rule.lhs.key, i))
+        .collect::<NoHashMap<TermKey, usize>>();
+    let mut cycles = Vec::<Vec<TermKey>>::new();
+    for rule in grammar.rules.iter() {
+        let start = rule.lhs.key;
+        let mut parents = NoHashMap::<TermKey, TermKey>::default();
+        let mut queue = VecDeque::from([start]);
+        let mut last = None;
+        while let Some(key) = queue.pop_front() {
+            for &corner in left_corners.get(&key).into_iter().flatten() {
+                if corner == start {
+                    last = Some(key);
+                    break;
+                }
+                if let std::collections::hash_map::Entry::Vacant(entry) = parents.entry(corner) {
+                    entry.insert(key);
+                    queue.push_back(corner);
+                }
+            }
+            if last.is_some() {
+                break;
+            }
+        }
+        let Some(mut key) = last else {
+            continue;
+        };
+        let mut cycle = vec![key];
+        while key != start {
+            key = parents[&key];
+            cycle.push(key);
+        }
+        cycle.reverse();
+        let first = (0..cycle.len())
+            .min_by_key(|&i| order[&cycle[i]])
+            .unwrap_or_default();
+        cycle.rotate_left(first);
+        if !cycles.contains(&cycle) {
+            cycles.push(cycle);
+        }
+    }
+    cycles
+}
+
+pub(crate) fn shortest_yields(grammar: &Grammar) -> NoHashMap<TermKey, String> {
+    // the candidates only ever get shorter, so this reaches a fixed point
+    let mut yields = NoHashMap::<TermKey, String>::default();
+    let mut was_updated = true;
+    while was_updated {
+        was_updated = false;
+        for rule in grammar.rules.iter() {
+            for terms in rule.rhs.alternatives.iter() {
+                let candidate = terms
+                    .iter()
+                    .map(|term| match term.is_terminal() {
+                        true => Some(term.content.clone()),
+                        false => yields.get(&term.key).cloned(),
+                    })
+                    .collect::<Option<String>>();
+                let Some(candidate) = candidate else {
+                    continue;
+                };
+                let is_shorter = yields
+                    .get(&rule.lhs.key)
+                    .is_none_or(|current| candidate.chars().count() < current.chars().count());
+                if is_shorter {
+                    yields.insert(rule.lhs.key, candidate);
+                    was_updated = true;
+                }
+            }
+        }
+    }
+    yields
+}
@@ -0,0 +1,96 @@
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::lr::LRKind;
+use crate::lr::LRParser;
+use crate::parser::ExtendedEarleyParser;
+use crate::parser::ParserTable;
+use crate::recovery::RecoveredParse;
+use crate::recovery::RecoveryCosts;
+use crate::sppf::SPPF;
+use std::rc::Rc;
+
+pub trait Parser {
+    fn recognize(&mut self, input: &str) -> bool;
+    fn check(&mut self, input: &str) -> Result<(), Error>;
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserBackend {
+    Earley,
+    CYK,
+    GLL,
+    LR(LRKind),
+}
+
+pub struct CompiledGrammar {
+    table: Rc<ParserTable>,
+    earley: ExtendedEarleyParser,
+    // built the first time an input is long enough for it to pay off, none if it has conflicts
+    lalr: Option<Option<LRParser>>,
+    size: usize,
+}
+
+impl CompiledGrammar {
+    pub fn parse_recovering(&mut self, input: &str) -> Result<RecoveredParse, Error> {
+        self.parse_recovering_parameterized(input, RecoveryCosts::default())
+    }
+
+    pub fn parse_recovering_parameterized(
+        &mut self,
+        input: &str,
+        costs: RecoveryCosts,
+    ) -> Result<RecoveredParse, Error> {
+        self.earley.parse_recovering(input, costs)
+    }
+
+    fn fastest_parser(&mut self, input: &str) -> &mut dyn Parser {
+        // building an LALR(1) automaton takes time roughly quadratic in the size of the grammar,
+        // which only pays off against the earley parser for inputs long enough, and only if the
+        // grammar turns out to be deterministic
+        if input.chars().count() > self.size * self.size / 4 {
+            // the automaton atomizes the terminals itself, its forests are over the source rules
+            let grammar = &self.table.source;
+            let lalr = self
+                .lalr
+                .get_or_insert_with(|| grammar.lr_parser(LRKind::LALR1).ok());
+            if let Some(parser) = lalr {
+                return parser;
+            }
+        }
+        &mut self.earley
+    }
+}
+
+impl Parser for CompiledGrammar {
+    fn recognize(&mut self, input: &str) -> bool {
+        self.fastest_parser(input).recognize(input)
+    }
+
+    fn check(&mut self, input: &str) -> Result<(), Error> {
+        self.fastest_parser(input).check(input)
+    }
+
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        self.fastest_parser(input).parse(input)
+    }
+}
+
+impl From<&Grammar> for CompiledGrammar {
+    fn from(grammar: &Grammar) -> Self {
+        let table = grammar.table();
+        let size = table
+            .grammar
+            .rules
+            .iter()
+            .flat_map(|rule| rule.rhs.alternatives.iter())
+            .map(|terms| terms.len() + 1)
+            .sum::<usize>();
+        Self {
+            earley: table.clone().into(),
+            table,
+            lalr: None,
+            size,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::alternatives::Alternatives;
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::reader::Reader;
+use crate::rule::Rule;
+use crate::term::Term;
+use std::rc::Rc;
+
+pub(crate) fn parse(text: &str) -> Result<Grammar, Error> {
+    let mut reader = Reader::new(text);
+    let mut builder = Grammar::builder();
+    reader.skip_whitespace();
+    while !reader.is_done() {
+        builder = builder.rule(rule(&mut reader)?);
+    }
+    builder.try_build()
+}
+
+fn rule(reader: &mut Reader) -> Result<Rule, Error> {
+    let lhs = nonterminal(reader)?;
+    reader.skip_whitespace();
+    reader.expect_str("::=")?;
+    reader.skip_whitespace();
+    let mut rhs = Alternatives::new();
+    loop {
+        let terms = alternative(reader)?;
+        let weight = reader.weight()?;
+        rhs.add_weighted_alternative(terms, weight)?;
+        reader.skip_whitespace();
+        if !reader.eat('|') {
+            break;
+        }
+        reader.skip_whitespace();
+    }
+    Ok(Rule {
+        lhs: Rc::new(lhs),
+        rhs,
+    })
+}
+
+fn alternative(reader: &mut Reader) -> Result<Vec<Term>, Error> {
+    let mut terms = Vec::new();
+    while !reader.is_done() && !matches!(reader.peek(), Some('|' | '@')) && !at_rule_start(reader) {
+        terms.push(term(reader)?);
+        reader.skip_whitespace();
+    }
+    if terms.is_empty() {
+        return Err(reader.unexpected("a term"));
+    }
+    Ok(terms)
+}
+
+fn at_rule_start(reader: &mut Reader) -> bool {
+    // rules are not terminated explicitly, a new one starts wherever a nonterminal is followed by
+    // the production symbol
+    let mark = reader.mark();
+    let found = nonterminal(reader).is_ok() && {
+        reader.skip_whitespace();
+        reader.eat_str("::=")
+    };
+    reader.reset(mark);
+    found
+}
+
+fn term(reader: &mut Reader) -> Result<Term, Error> {
+    match reader.peek() {
+        Some('<') => nonterminal(reader),
+        Some(quote @ ('"' | '\'')) => Ok(Term::terminal(&reader.escaped_literal(quote)?)),
+        _ => Err(reader.unexpected("a term")),
+    }
+}
+
+fn nonterminal(reader: &mut Reader) -> Result<Term, Error> {
+    let start = reader.mark();
+    if !reader.eat('<') {
+        return Err(reader.unexpected("'<'"));
+    }
+    let name = reader.take_while(|c| !matches!(c, '<' | '>' | '\n'));
+    if !reader.eat('>') {
+        return Err(reader.unexpected("'>'"));
+    }
+    if name.trim().is_empty() {
+        return Err(reader.error_at(start, "nonterminal name must not be empty"));
+    }
+    Ok(Term::nonterminal(name.trim()))
+}
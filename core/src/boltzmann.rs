@@ -0,0 +1,209 @@
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::term::Term;
+use crate::transform;
+use crate::types::FxHashMap;
+use crate::types::NoHashMap;
+use crate::types::TermKey;
+use std::rc::Rc;
+
+// newton iterations before giving up on the generating functions converging
+const MAX_ITERATIONS: usize = 200;
+const TOLERANCE: f64 = 1e-12;
+
+struct System<'a> {
+    grammar: &'a Grammar,
+    indices: NoHashMap<TermKey, usize>,
+}
+
+impl System<'_> {
+    fn evaluate(&self, x: f64, values: &[f64]) -> (Vec<f64>, Vec<Vec<f64>>, Vec<f64>) {
+        // the generating functions of the rules, their partial derivatives in the values of the
+        // nonterminals and in x, where each character of output contributes a factor of x and
+        // each alternative one of its weight
+        let n = values.len();
+        let mut functions = vec![0.0; n];
+        let mut jacobian = vec![vec![0.0; n]; n];
+        let mut derivatives = vec![0.0; n];
+        for (i, rule) in self.grammar.rules.iter().enumerate() {
+            for (terms, weight) in rule.rhs.alternatives.iter().zip(rule.rhs.weights.iter()) {
+                let factors = terms
+                    .iter()
+                    .map(|term| self.value(term, x, values))
+                    .collect::<Vec<f64>>();
+                functions[i] += weight * factors.iter().product::<f64>();
+                for (j, term) in terms.iter().enumerate() {
+                    let others = weight
+                        * factors
+                            .iter()
+                            .enumerate()
+                            .filter(|(k, _)| *k != j)
+                            .map(|(_, factor)| factor)
+                            .product::<f64>();
+                    match term.is_terminal() {
+                        true => {
+                            let length = term.content.chars().count() as i32;
+                            if length > 0 {
+                                derivatives[i] += length as f64 * x.powi(length - 1) * others;
+                            }
+                        }
+                        false => {
+                            if let Some(&k) = self.indices.get(&term.key) {
+                                jacobian[i][k] += others;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (functions, jacobian, derivatives)
+    }
+
+    fn value(&self, term: &Term, x: f64, values: &[f64]) -> f64 {
+        match term.is_terminal() {
+            true => x.powi(term.content.chars().count() as i32),
+            false => self.indices.get(&term.key).map_or(0.0, |&i| values[i]),
+        }
+    }
+
+    fn solve(&self, x: f64) -> Option<(Vec<f64>, f64)> {
+        // newton iteration from zero increases towards the smallest solution of the system, which
+        // only exists below the singularity, past it the iterates diverge or turn negative
+        let n = self.grammar.rules.len();
+        let mut values = vec![0.0; n];
+        let mut is_converged = false;
+        for _ in 0..MAX_ITERATIONS {
+            let (functions, jacobian, _) = self.evaluate(x, &values);
+            let residuals = (0..n).map(|i| functions[i] - values[i]).collect();
+            let delta = solve_linear(identity_minus(jacobian), residuals)?;
+            for (value, delta) in values.iter_mut().zip(delta.iter()) {
+                *value += delta;
+            }
+            if values
+                .iter()
+                .any(|value| !value.is_finite() || *value < 0.0)
+            {
+                return None;
+            }
+            is_converged = delta
+                .iter()
+                .zip(values.iter())
+                .all(|(delta, value)| delta.abs() <= TOLERANCE * value.max(1.0));
+            if is_converged {
+                break;
+            }
+        }
+        if !is_converged {
+            return None;
+        }
+        // the derivatives of the values in x give the expected size at the start symbol
+        let (_, jacobian, derivatives) = self.evaluate(x, &values);
+        let slopes = solve_linear(identity_minus(jacobian), derivatives)?;
+        if slopes
+            .iter()
+            .any(|slope| !slope.is_finite() || *slope < 0.0)
+        {
+            return None;
+        }
+        let start = self.indices[&self.grammar.start];
+        let expected_size = x * slopes[start] / values[start];
+        expected_size.is_finite().then_some((values, expected_size))
+    }
+}
+
+pub(crate) fn tune(
+    grammar: &Grammar,
+    expected_size: usize,
+) -> Result<FxHashMap<*const Vec<Term>, f64>, Error> {
+    // the expected size grows with x up to the singularity of the generating functions, if it
+    // stays below the target there, the largest x still converging is the best available
+    let grammar = &transform::remove_useless_symbols(grammar);
+    let system = System {
+        grammar,
+        indices: grammar
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| (rule.lhs.key, i))
+            .collect(),
+    };
+    let target = expected_size as f64;
+    let is_below = |x: f64| {
+        system
+            .solve(x)
+            .is_some_and(|(_, expected_size)| expected_size <= target)
+    };
+    let (mut lower, mut upper) = (0.0, 1.0);
+    while upper < 1e6 && is_below(upper) {
+        lower = upper;
+        upper *= 2.0;
+    }
+    for _ in 0..64 {
+        let x = (lower + upper) / 2.0;
+        match is_below(x) {
+            true => lower = x,
+            false => upper = x,
+        }
+    }
+    let Some((values, _)) = system.solve(lower).filter(|_| lower > 0.0) else {
+        return Err(Error::GenerationBoundsError(
+            "cannot tune a boltzmann sampler, the generating functions of the grammar do not \
+             converge"
+                .to_owned(),
+        ));
+    };
+    let mut weights = FxHashMap::<*const Vec<Term>, f64>::default();
+    for rule in grammar.rules.iter() {
+        for (terms, weight) in rule.rhs.alternatives.iter().zip(rule.rhs.weights.iter()) {
+            let weight = weight
+                * terms
+                    .iter()
+                    .map(|term| system.value(term, lower, &values))
+                    .product::<f64>();
+            weights.insert(Rc::as_ptr(terms), weight);
+        }
+    }
+    Ok(weights)
+}
+
+fn identity_minus(mut matrix: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    for (i, row) in matrix.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (i == j) as u8 as f64 - *value;
+        }
+    }
+    matrix
+}
+
+fn solve_linear(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    // gaussian elimination with partial pivoting
+    let n = rhs.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() < f64::MIN_POSITIVE {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        for row in column + 1..n {
+            let factor = matrix[row][column] / matrix[column][column];
+            if factor == 0.0 {
+                continue;
+            }
+            let (above, below) = matrix.split_at_mut(row);
+            for (value, pivot) in below[0][column..].iter_mut().zip(&above[column][column..]) {
+                *value -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[column];
+        }
+    }
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n)
+            .map(|k| matrix[row][k] * solution[k])
+            .sum::<f64>();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
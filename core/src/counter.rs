@@ -0,0 +1,243 @@
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::term::Term;
+use crate::types::NoHashMap;
+use crate::types::TermKey;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::RngCore;
+
+// none where there are infinitely many derivations
+type Count = Option<BigUint>;
+
+pub(crate) struct DerivationCounter<'a> {
+    grammar: &'a Grammar,
+    // counts[nonterminal][length]
+    counts: NoHashMap<TermKey, Vec<Count>>,
+    // prefixes[nonterminal][alternative][i][length] counts the derivations of the first i terms
+    prefixes: NoHashMap<TermKey, Vec<Vec<Vec<Count>>>>,
+}
+
+impl<'a> DerivationCounter<'a> {
+    pub(crate) fn new(grammar: &'a Grammar, max_length: usize) -> Self {
+        let mut counter = Self {
+            grammar,
+            counts: Default::default(),
+            prefixes: Default::default(),
+        };
+        for rule in grammar.rules.iter() {
+            let alternatives = rule.rhs.alternatives.iter();
+            let prefixes = alternatives.map(|terms| vec![Vec::new(); terms.len() + 1]);
+            counter.prefixes.insert(rule.lhs.key, prefixes.collect());
+        }
+        for length in 0..=max_length {
+            counter.fill(length);
+        }
+        counter
+    }
+
+    fn fill(&mut self, length: usize) {
+        // derivations of one length can depend on each other through unit and nullable
+        // alternatives, so the counts are iterated from zero until they settle, and any count
+        // still growing after twice as many rounds as it takes to pass through every rule once
+        // belongs to a cycle, and is infinite
+        let rounds = self.grammar.rules.len() + 1;
+        let mut current = self
+            .grammar
+            .rules
+            .iter()
+            .map(|rule| (rule.lhs.key, Some(BigUint::ZERO)))
+            .collect::<NoHashMap<TermKey, Count>>();
+        let mut settled = None;
+        for round in 0..2 * rounds {
+            if round == rounds {
+                settled = Some(current.clone());
+            }
+            let next = self
+                .grammar
+                .rules
+                .iter()
+                .map(|rule| {
+                    let alternatives = rule.rhs.alternatives.iter();
+                    let count = alternatives
+                        .zip(self.prefixes[&rule.lhs.key].iter())
+                        .map(|(terms, rows)| self.row(terms, rows, length, &current).pop().unwrap())
+                        .try_fold(BigUint::ZERO, |acc, count| Some(acc + count?));
+                    (rule.lhs.key, count)
+                })
+                .collect::<NoHashMap<TermKey, Count>>();
+            if next == current {
+                settled = None;
+                break;
+            }
+            current = next;
+        }
+        for (key, count) in current.iter_mut() {
+            if settled
+                .as_ref()
+                .is_some_and(|settled| settled[key] != *count)
+            {
+                *count = None;
+            }
+        }
+        for rule in self.grammar.rules.iter() {
+            for (i, terms) in rule.rhs.alternatives.iter().enumerate() {
+                let row = self.row(terms, &self.prefixes[&rule.lhs.key][i], length, &current);
+                let prefixes = &mut self.prefixes.get_mut(&rule.lhs.key).unwrap()[i];
+                for (prefix, count) in prefixes.iter_mut().zip(row) {
+                    prefix.push(count);
+                }
+            }
+        }
+        for (key, count) in current {
+            self.counts.entry(key).or_default().push(count);
+        }
+    }
+
+    fn row(
+        &self,
+        terms: &[Term],
+        rows: &[Vec<Count>],
+        length: usize,
+        current: &NoHashMap<TermKey, Count>,
+    ) -> Vec<Count> {
+        // the derivations of each prefix of the terms of exactly this length, from the stored
+        // rows of the shorter lengths and the current estimates for this one
+        let mut row = Vec::with_capacity(terms.len() + 1);
+        row.push(Some(BigUint::from((length == 0) as u32)));
+        for (i, term) in terms.iter().enumerate() {
+            let count = (0..=length)
+                .map(|n| {
+                    let prefix = match n {
+                        0 => &row[i],
+                        _ => &rows[i][length - n],
+                    };
+                    let count = match n == length && term.is_nonterminal() {
+                        true => current
+                            .get(&term.key)
+                            .cloned()
+                            .unwrap_or(Some(BigUint::ZERO)),
+                        false => self.count(term, n),
+                    };
+                    mul(prefix, &count)
+                })
+                .try_fold(BigUint::ZERO, |acc, count| Some(acc + count?));
+            row.push(count);
+        }
+        row
+    }
+
+    fn count(&self, term: &Term, length: usize) -> Count {
+        match term.is_terminal() {
+            true => Some(BigUint::from(
+                (term.content.chars().count() == length) as u32,
+            )),
+            false => self.stored(term.key, length),
+        }
+    }
+
+    fn stored(&self, key: TermKey, length: usize) -> Count {
+        self.counts
+            .get(&key)
+            .map_or(Some(BigUint::ZERO), |counts| counts[length].clone())
+    }
+
+    pub(crate) fn count_derivations(&self, length: usize) -> Count {
+        self.stored(self.grammar.start, length)
+    }
+
+    pub(crate) fn sample(&self, length: usize, rng: &mut StdRng) -> Result<(String, usize), Error> {
+        // each derivation of the given length is equally likely, which makes each word equally
+        // likely if the grammar is unambiguous, the word comes with the expansions it took
+        match self.count_derivations(length) {
+            None => {
+                return Err(Error::InfinitelyRecursiveProductionError(format!(
+                    "cannot sample uniformly among infinitely many derivations of length {}",
+                    length
+                )))
+            }
+            Some(count) if count == BigUint::ZERO => {
+                return Err(Error::GenerationBoundsError(format!(
+                    "there is no word of length {}",
+                    length
+                )))
+            }
+            _ => {}
+        }
+        let mut word = String::new();
+        let mut steps = 0;
+        let mut stack = vec![(self.grammar.start, length)];
+        while let Some((key, length)) = stack.pop() {
+            let Some(term) = self.grammar.term(key).filter(|term| term.is_terminal()) else {
+                stack.extend(self.expand(key, length, rng));
+                steps += 1;
+                continue;
+            };
+            word.push_str(&term.content);
+        }
+        Ok((word, steps))
+    }
+
+    fn expand(&self, key: TermKey, length: usize, rng: &mut StdRng) -> Vec<(TermKey, usize)> {
+        // only nonterminals with a rule are ever counted, anything else expands to nothing
+        let (Some(rule), Some(prefixes)) = (self.grammar.rule(key), self.prefixes.get(&key)) else {
+            return Vec::new();
+        };
+        let weights = prefixes
+            .iter()
+            .map(|rows| rows.last().unwrap()[length].clone().unwrap_or_default())
+            .collect::<Vec<BigUint>>();
+        let alternative = choose(&weights, rng);
+        let terms = &rule.rhs.alternatives[alternative];
+        let rows = &prefixes[alternative];
+        // the lengths are split off from the back, each weighted by the derivations it leaves
+        let mut remaining = length;
+        let mut expansion = Vec::with_capacity(terms.len());
+        for (i, term) in terms.iter().enumerate().rev() {
+            let weights = (0..=remaining)
+                .map(|n| mul(&rows[i][remaining - n], &self.count(term, n)).unwrap_or_default())
+                .collect::<Vec<BigUint>>();
+            let n = choose(&weights, rng);
+            expansion.push((term.key, n));
+            remaining -= n;
+        }
+        expansion
+    }
+}
+
+fn mul(a: &Count, b: &Count) -> Count {
+    // no derivations of one part means none of the whole, even if the other has infinitely many
+    if [a, b].contains(&&Some(BigUint::ZERO)) {
+        return Some(BigUint::ZERO);
+    }
+    Some(a.as_ref()? * b.as_ref()?)
+}
+
+fn choose(weights: &[BigUint], rng: &mut StdRng) -> usize {
+    let total = weights.iter().sum::<BigUint>();
+    let mut target = random_below(&total, rng);
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+    unreachable!("the target is below the sum of the weights")
+}
+
+fn random_below(bound: &BigUint, rng: &mut StdRng) -> BigUint {
+    // rejection sampling among the numbers with as many bits as the bound
+    let bits = bound.bits();
+    let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+    let excess = bytes.len() as u64 * 8 - bits;
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if let Some(last) = bytes.last_mut() {
+            *last &= 0xff >> excess;
+        }
+        let candidate = BigUint::from_bytes_le(&bytes);
+        if candidate < *bound {
+            return candidate;
+        }
+    }
+}
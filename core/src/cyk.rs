@@ -0,0 +1,323 @@
+use crate::analysis;
+use crate::backend::Parser;
+use crate::error::Error;
+use crate::error::SyntaxError;
+use crate::grammar::Grammar;
+use crate::sppf::Origins;
+use crate::sppf::SPPFNodeLabel;
+use crate::sppf::SPPF;
+use crate::term::Term;
+use crate::transform;
+use crate::types::FxHashMap;
+use crate::types::FxHashSet;
+use crate::types::NoHashMap;
+use crate::types::NoHashSet;
+use crate::types::TermKey;
+
+struct Binary {
+    lhs: TermKey,
+    left: TermKey,
+    right: TermKey,
+}
+
+pub struct CYKParser {
+    // the chomsky normal form decides which spans the nonterminals derive, the forests are built
+    // over the rules of the grammar itself
+    grammar: Grammar,
+    origins: Origins,
+    nullable: NoHashSet<TermKey>,
+    unaries: FxHashMap<char, Vec<TermKey>>,
+    binaries: Vec<Binary>,
+    by_left: NoHashMap<TermKey, Vec<usize>>,
+    by_lhs: NoHashMap<TermKey, Vec<usize>>,
+    input: Vec<char>,
+    table: Vec<Vec<NoHashSet<TermKey>>>,
+}
+
+impl CYKParser {
+    fn fill(&mut self, input: &str) {
+        // table[start][length - 1] holds the nonterminals deriving the input of that span
+        self.input = input.chars().collect();
+        let n = self.input.len();
+        self.table = (0..n)
+            .map(|start| vec![NoHashSet::default(); n - start])
+            .collect();
+        for (start, c) in self.input.iter().enumerate() {
+            let unaries = self.unaries.get(c).into_iter().flatten();
+            self.table[start][0] = unaries.copied().collect();
+        }
+        for length in 2..=n {
+            for start in 0..=n - length {
+                let mut cell = NoHashSet::default();
+                for split in 1..length {
+                    let right = &self.table[start + split][length - split - 1];
+                    for left in self.table[start][split - 1].iter() {
+                        for &i in self.by_left.get(left).into_iter().flatten() {
+                            if right.contains(&self.binaries[i].right) {
+                                cell.insert(self.binaries[i].lhs);
+                            }
+                        }
+                    }
+                }
+                self.table[start][length - 1] = cell;
+            }
+        }
+    }
+
+    fn derives(&self, key: TermKey, start: usize, end: usize) -> bool {
+        end > start && self.table[start][end - start - 1].contains(&key)
+    }
+
+    fn derives_term(&self, term: &Term, start: usize, end: usize) -> bool {
+        match term.is_terminal() {
+            true => end == start + 1 && term.content.chars().eq([self.input[start]]),
+            false if start == end => self.nullable.contains(&term.key),
+            false => self.derives(term.key, start, end),
+        }
+    }
+
+    fn accepts(&self, end: usize) -> bool {
+        match end {
+            0 => self.nullable.contains(&self.grammar.start),
+            _ => self.derives(self.grammar.start, 0, end),
+        }
+    }
+
+    fn error_position(&self) -> usize {
+        // prefixes[start][length - 1] holds the nonterminals deriving some string which begins
+        // with the input of that span, parsing fails right after the longest such prefix
+        let n = self.input.len();
+        let mut prefixes = Vec::<Vec<NoHashSet<TermKey>>>::with_capacity(n);
+        for start in 0..n {
+            prefixes.push(vec![NoHashSet::default(); n - start]);
+        }
+        for length in 1..=n {
+            for start in 0..=n - length {
+                let mut cell = self.table[start][length - 1].clone();
+                for split in 1..length {
+                    let right = &prefixes[start + split][length - split - 1];
+                    for left in self.table[start][split - 1].iter() {
+                        for &i in self.by_left.get(left).into_iter().flatten() {
+                            if right.contains(&self.binaries[i].right) {
+                                cell.insert(self.binaries[i].lhs);
+                            }
+                        }
+                    }
+                }
+                self.close_prefixes(&mut cell);
+                prefixes[start][length - 1] = cell;
+            }
+        }
+        (1..=n)
+            .rev()
+            .find(|&length| prefixes[0][length - 1].contains(&self.grammar.start))
+            .unwrap_or_default()
+    }
+
+    fn close_prefixes(&self, cell: &mut NoHashSet<TermKey>) {
+        // whatever begins with a string some left nonterminal begins with, also begins with it
+        let mut pending = cell.iter().copied().collect::<Vec<TermKey>>();
+        while let Some(left) = pending.pop() {
+            for &i in self.by_left.get(&left).into_iter().flatten() {
+                if cell.insert(self.binaries[i].lhs) {
+                    pending.push(self.binaries[i].lhs);
+                }
+            }
+        }
+    }
+
+    fn expected(&self, position: usize) -> Vec<char> {
+        // goals[start] holds the nonterminals which would extend the input to a prefix of a word
+        // by deriving the input from start up to the position followed by some character, either
+        // exactly or as the beginning of a longer string, worked out from the start symbol on
+        let mut goals = vec![(NoHashSet::<TermKey>::default(), NoHashSet::default()); position + 1];
+        goals[0].1.insert(self.grammar.start);
+        for start in 0..=position {
+            let (mut exact, mut prefix) = std::mem::take(&mut goals[start]);
+            // whatever begins the words of a nonterminal also begins those of its left children
+            let mut pending = prefix.iter().copied().collect::<Vec<TermKey>>();
+            while let Some(lhs) = pending.pop() {
+                for &i in self.by_lhs.get(&lhs).into_iter().flatten() {
+                    if prefix.insert(self.binaries[i].left) {
+                        pending.push(self.binaries[i].left);
+                    }
+                }
+            }
+            exact.extend(prefix.iter().copied());
+            let lefts = self.table.get(start).into_iter().flatten();
+            for (goal, lefts) in goals[start + 1..].iter_mut().zip(lefts) {
+                for left in lefts.iter() {
+                    for &i in self.by_left.get(left).into_iter().flatten() {
+                        let binary = &self.binaries[i];
+                        if exact.contains(&binary.lhs) {
+                            goal.0.insert(binary.right);
+                        }
+                        if prefix.contains(&binary.lhs) {
+                            goal.1.insert(binary.right);
+                        }
+                    }
+                }
+            }
+            goals[start] = (exact, prefix);
+        }
+        let mut expected = (self.unaries.iter())
+            .filter(|(_, lhs)| lhs.iter().any(|lhs| goals[position].0.contains(lhs)))
+            .map(|(c, _)| *c)
+            .collect::<Vec<char>>();
+        expected.sort_unstable();
+        expected
+    }
+
+    fn ends(&self, expression: &[Term], start: usize, end: usize) -> Vec<Vec<usize>> {
+        // ends[dot] holds the positions up to which the first dot terms derive from the start
+        let mut ends = vec![vec![start]];
+        for term in expression.iter() {
+            let mut next = (ends.last().unwrap().iter())
+                .flat_map(|&pivot| {
+                    (pivot..=end).filter(move |&to| self.derives_term(term, pivot, to))
+                })
+                .collect::<Vec<usize>>();
+            next.sort_unstable();
+            next.dedup();
+            ends.push(next);
+        }
+        ends
+    }
+
+    fn forest(&self, root: &Term) -> SPPF {
+        // the symbols are expanded from the root down, each alternative back from its end, over
+        // the positions its prefixes can end at
+        let n = self.input.len();
+        let label = SPPFNodeLabel::symbol(root, 0, n);
+        let mut sppf = SPPF::new(label.clone());
+        let mut visited = FxHashSet::from_iter([label]);
+        let mut pending = vec![(root.clone(), 0, n)];
+        while let Some((term, start, end)) = pending.pop() {
+            let label = SPPFNodeLabel::symbol(&term, start, end);
+            sppf.insert(label.clone());
+            let Some(rule) = self.grammar.rule(term.key) else {
+                continue;
+            };
+            for expression in rule.rhs.alternatives.iter() {
+                if expression.is_empty() {
+                    if start == end {
+                        sppf.add_packed(&label, expression, start, None, None);
+                    }
+                    continue;
+                }
+                let ends = self.ends(expression, start, end);
+                let mut items = vec![(expression.len(), end)];
+                let mut seen = FxHashSet::<(usize, usize)>::default();
+                while let Some((dot, item_end)) = items.pop() {
+                    let item = match dot == expression.len() {
+                        true => label.clone(),
+                        false => SPPFNodeLabel::prefix(expression, dot, start, item_end),
+                    };
+                    let last = &expression[dot - 1];
+                    for &pivot in ends[dot - 1].iter() {
+                        if pivot > item_end || !self.derives_term(last, pivot, item_end) {
+                            continue;
+                        }
+                        let right = SPPFNodeLabel::symbol(last, pivot, item_end);
+                        if visited.insert(right.clone()) {
+                            pending.push((last.clone(), pivot, item_end));
+                        }
+                        let left = match dot {
+                            1 => None,
+                            // a prefix of length one is represented by the node of its only symbol
+                            2 => {
+                                let first = SPPFNodeLabel::symbol(&expression[0], start, pivot);
+                                if visited.insert(first.clone()) {
+                                    pending.push((expression[0].clone(), start, pivot));
+                                }
+                                Some(first)
+                            }
+                            _ => {
+                                if seen.insert((dot - 1, pivot)) {
+                                    items.push((dot - 1, pivot));
+                                }
+                                Some(SPPFNodeLabel::prefix(expression, dot - 1, start, pivot))
+                            }
+                        };
+                        sppf.add_packed(&item, expression, pivot, left, Some(right));
+                    }
+                }
+            }
+        }
+        sppf.restore_terminals(&self.origins)
+    }
+}
+
+impl Parser for CYKParser {
+    fn recognize(&mut self, input: &str) -> bool {
+        self.fill(input);
+        self.accepts(self.input.len())
+    }
+
+    fn check(&mut self, input: &str) -> Result<(), Error> {
+        self.grammar.start_rule()?;
+        if self.recognize(input) {
+            return Ok(());
+        }
+        let position = self.error_position();
+        Err(Error::SyntaxError(SyntaxError::new(
+            &self.input,
+            position,
+            self.expected(position),
+            position < self.input.len() && self.accepts(position),
+        )))
+    }
+
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        self.check(input)?;
+        let root = self.grammar.start_rule()?.lhs.clone();
+        Ok(self.forest(&root))
+    }
+}
+
+impl From<&Grammar> for CYKParser {
+    fn from(grammar: &Grammar) -> Self {
+        // nonterminals only reached through unit productions are kept in the normal form, as
+        // the forests need to know the spans they derive
+        let normal_form = transform::chomsky_normal_form(grammar, true);
+        let mut grammar = grammar.clone();
+        let origins = grammar.atomize_terminals();
+        let mut unaries = FxHashMap::<char, Vec<TermKey>>::default();
+        let mut binaries = Vec::new();
+        let mut by_left = NoHashMap::<TermKey, Vec<usize>>::default();
+        let mut by_lhs = NoHashMap::<TermKey, Vec<usize>>::default();
+        for rule in normal_form.rules.iter() {
+            for expression in rule.rhs.alternatives.iter() {
+                match expression.as_slice() {
+                    [left, right] => {
+                        by_left.entry(left.key).or_default().push(binaries.len());
+                        by_lhs.entry(rule.lhs.key).or_default().push(binaries.len());
+                        binaries.push(Binary {
+                            lhs: rule.lhs.key,
+                            left: left.key,
+                            right: right.key,
+                        });
+                    }
+                    // the empty word is accepted from the nullable nonterminals of the grammar
+                    [terminal] if terminal.is_epsilon() => (),
+                    [terminal] => {
+                        let c = terminal.content.chars().next().unwrap();
+                        unaries.entry(c).or_default().push(rule.lhs.key);
+                    }
+                    _ => unreachable!("alternatives in chomsky normal form have one or two terms"),
+                }
+            }
+        }
+        Self {
+            nullable: analysis::nullable_nonterminals(&grammar),
+            grammar,
+            origins,
+            unaries,
+            binaries,
+            by_left,
+            by_lhs,
+            input: Vec::new(),
+            table: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use crate::alternatives::Alternatives;
+use crate::error::Error;
+use crate::grammar::Grammar;
+use crate::reader::Mark;
+use crate::reader::Reader;
+use crate::rule::Rule;
+use crate::term::Term;
+use crate::types::FxHashMap;
+use crate::types::NoHashMap;
+use crate::types::TermKey;
+use itertools::Itertools;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+const MAX_EXCEPTION_WORDS: usize = 1 << 16;
+
+struct Exception {
+    helper: Term,
+    minuend: Vec<Vec<Term>>,
+    subtrahend: Vec<Vec<Term>>,
+    mark: Mark,
+}
+
+struct EBNFReader {
+    reader: Reader,
+    rules: Vec<Rule>,
+    helpers: Vec<Rule>,
+    exceptions: Vec<Exception>,
+    current: String,
+    n_helpers: usize,
+}
+
+pub(crate) fn parse(text: &str) -> Result<Grammar, Error> {
+    let mut ebnf = EBNFReader {
+        reader: Reader::new(text),
+        rules: Vec::new(),
+        helpers: Vec::new(),
+        exceptions: Vec::new(),
+        current: String::new(),
+        n_helpers: 0,
+    };
+    ebnf.syntax()?;
+    ebnf.resolve_exceptions()?;
+    Grammar::builder()
+        .rules(&ebnf.rules)
+        .helpers(&ebnf.helpers)
+        .try_build()
+}
+
+impl EBNFReader {
+    fn skip_gaps(&mut self) -> Result<(), Error> {
+        loop {
+            self.reader.skip_whitespace();
+            let start = self.reader.mark();
+            if !self.reader.eat_str("(*") {
+                return Ok(());
+            }
+            let mut depth = 1;
+            while depth > 0 {
+                if self.reader.eat_str("(*") {
+                    depth += 1;
+                } else if self.reader.eat_str("*)") {
+                    depth -= 1;
+                } else if self.reader.next().is_none() {
+                    return Err(self.reader.error_at(start, "unterminated comment"));
+                }
+            }
+        }
+    }
+
+    fn syntax(&mut self) -> Result<(), Error> {
+        self.skip_gaps()?;
+        while !self.reader.is_done() {
+            self.rule()?;
+            self.skip_gaps()?;
+        }
+        Ok(())
+    }
+
+    fn rule(&mut self) -> Result<(), Error> {
+        let name = match self.reader.peek() {
+            Some('?') => self.special_sequence()?,
+            _ => self.meta_identifier()?,
+        };
+        self.current = name.clone();
+        self.skip_gaps()?;
+        if !self.reader.eat('=') {
+            return Err(self.reader.unexpected("'='"));
+        }
+        let mut weights = Vec::new();
+        let alternatives = self.definitions_list(Some(&mut weights))?;
+        if !self.reader.eat(';') && !self.reader.eat('.') {
+            return Err(self.reader.unexpected("';'"));
+        }
+        self.rules.push(Rule {
+            lhs: Rc::new(Term::nonterminal(&name)),
+            rhs: Alternatives::try_from(alternatives.into_iter().zip(weights).collect::<Vec<_>>())?,
+        });
+        Ok(())
+    }
+
+    fn meta_identifier(&mut self) -> Result<String, Error> {
+        // gaps inside meta identifiers are insignificant, multiword names are kept space separated
+        let mut words = Vec::new();
+        while self.reader.peek().is_some_and(|c| c.is_alphabetic()) {
+            words.push(self.reader.take_while(|c| c.is_alphanumeric() || c == '_'));
+            let mark = self.reader.mark();
+            self.skip_gaps()?;
+            if !self.reader.peek().is_some_and(|c| c.is_alphanumeric()) {
+                self.reader.reset(mark);
+            }
+        }
+        match words.is_empty() {
+            true => Err(self.reader.unexpected("a meta identifier")),
+            false => Ok(words.join(" ")),
+        }
+    }
+
+    fn special_sequence(&mut self) -> Result<String, Error> {
+        // the meaning of special sequences is left to the implementation, here they name
+        // nonterminals which are no valid meta identifiers
+        let start = self.reader.mark();
+        self.reader.next();
+        let content = self.reader.take_while(|c| c != '?');
+        if !self.reader.eat('?') {
+            return Err(self.reader.error_at(start, "unterminated special sequence"));
+        }
+        match content.trim() {
+            "" => Err(self
+                .reader
+                .error_at(start, "special sequences must not be empty")),
+            name => Ok(name.to_owned()),
+        }
+    }
+
+    fn definitions_list(
+        &mut self,
+        mut weights: Option<&mut Vec<f64>>,
+    ) -> Result<Vec<Vec<Term>>, Error> {
+        // only the definitions of a rule itself can be weighted, not those nested in brackets
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(self.single_definition()?);
+            if let Some(weights) = weights.as_deref_mut() {
+                weights.push(self.reader.weight()?);
+                self.skip_gaps()?;
+            }
+            if !(self.reader.eat('|')
+                || (self.reader.peek() == Some('/') && self.reader.peek_nth(1) != Some(')'))
+                    && self.reader.eat('/')
+                || self.reader.eat('!'))
+            {
+                return Ok(alternatives);
+            }
+        }
+    }
+
+    fn at_definition_end(&mut self) -> Result<bool, Error> {
+        self.skip_gaps()?;
+        Ok(self.reader.is_done()
+            || matches!(
+                self.reader.peek(),
+                Some('|' | '/' | '!' | ';' | '.' | ')' | ']' | '}' | '@')
+            )
+            || (self.reader.peek() == Some(':') && self.reader.peek_nth(1) == Some(')')))
+    }
+
+    fn single_definition(&mut self) -> Result<Vec<Term>, Error> {
+        if self.at_definition_end()? {
+            return Ok(vec![Term::terminal("")]);
+        }
+        let mut terms = self.syntactic_term()?;
+        self.skip_gaps()?;
+        while self.reader.eat(',') {
+            terms.append(&mut self.syntactic_term()?);
+            self.skip_gaps()?;
+        }
+        Ok(terms)
+    }
+
+    fn syntactic_term(&mut self) -> Result<Vec<Term>, Error> {
+        let mark = self.reader.mark();
+        let minuend = self.syntactic_factor()?;
+        self.skip_gaps()?;
+        if !self.reader.eat('-') {
+            return Ok(self.splice(minuend));
+        }
+        let subtrahend = self.syntactic_factor()?;
+        let helper = self.helper("except");
+        self.exceptions.push(Exception {
+            helper: helper.clone(),
+            minuend,
+            subtrahend,
+            mark,
+        });
+        Ok(vec![helper])
+    }
+
+    fn syntactic_factor(&mut self) -> Result<Vec<Vec<Term>>, Error> {
+        self.skip_gaps()?;
+        if !self.reader.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return self.syntactic_primary();
+        }
+        let count = self
+            .reader
+            .take_while(|c| c.is_ascii_digit())
+            .parse::<usize>()
+            .map_err(|_| self.reader.error("repetition count is too large"))?;
+        self.skip_gaps()?;
+        if !self.reader.eat('*') {
+            return Err(self.reader.unexpected("'*'"));
+        }
+        let primary = self.syntactic_primary()?;
+        let terms = self.splice(primary);
+        Ok(vec![terms
+            .iter()
+            .cycle()
+            .take(terms.len() * count)
+            .cloned()
+            .collect()])
+    }
+
+    fn syntactic_primary(&mut self) -> Result<Vec<Vec<Term>>, Error> {
+        self.skip_gaps()?;
+        let start = self.reader.mark();
+        if self.reader.eat('[') || self.reader.eat_str("(/") {
+            let mut alternatives = self.definitions_list(None)?;
+            self.close(&["]", "/)"])?;
+            alternatives.push(vec![Term::terminal("")]);
+            return Ok(vec![vec![self.add_helper("opt", alternatives)]]);
+        }
+        if self.reader.eat('{') || self.reader.eat_str("(:") {
+            let alternatives = self.definitions_list(None)?;
+            self.close(&["}", ":)"])?;
+            let helper = self.helper("rep");
+            let mut repetition = vec![vec![Term::terminal("")]];
+            repetition.extend(alternatives.into_iter().map(|mut terms| {
+                terms.push(helper.clone());
+                terms
+            }));
+            self.add_rule(&helper, repetition);
+            return Ok(vec![vec![helper]]);
+        }
+        if self.reader.eat('(') {
+            let alternatives = self.definitions_list(None)?;
+            self.close(&[")"])?;
+            return Ok(alternatives);
+        }
+        match self.reader.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.reader.next();
+                let content = self.reader.take_while(|c| c != quote);
+                if !self.reader.eat(quote) {
+                    return Err(self.reader.error_at(start, "unterminated terminal string"));
+                }
+                if content.is_empty() {
+                    return Err(self
+                        .reader
+                        .error_at(start, "terminal strings must not be empty"));
+                }
+                Ok(vec![vec![Term::terminal(&content)]])
+            }
+            Some('?') => Ok(vec![vec![Term::nonterminal(&self.special_sequence()?)]]),
+            Some(c) if c.is_alphabetic() => {
+                Ok(vec![vec![Term::nonterminal(&self.meta_identifier()?)]])
+            }
+            _ => Err(self.reader.unexpected("a syntactic primary")),
+        }
+    }
+
+    fn close(&mut self, symbols: &[&str]) -> Result<(), Error> {
+        self.skip_gaps()?;
+        match symbols.iter().any(|symbol| self.reader.eat_str(symbol)) {
+            true => Ok(()),
+            false => Err(self.reader.unexpected(&format!("'{}'", symbols[0]))),
+        }
+    }
+
+    fn splice(&mut self, mut alternatives: Vec<Vec<Term>>) -> Vec<Term> {
+        match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => vec![self.add_helper("group", alternatives)],
+        }
+    }
+
+    fn helper(&mut self, kind: &str) -> Term {
+        self.n_helpers += 1;
+        Term::nonterminal(&format!("{}.{}{}", self.current, kind, self.n_helpers))
+    }
+
+    fn add_helper(&mut self, kind: &str, alternatives: Vec<Vec<Term>>) -> Term {
+        let helper = self.helper(kind);
+        self.add_rule(&helper, alternatives);
+        helper
+    }
+
+    fn add_rule(&mut self, lhs: &Term, alternatives: Vec<Vec<Term>>) {
+        self.helpers.push(Rule {
+            lhs: Rc::new(lhs.clone()),
+            rhs: Alternatives::from(alternatives),
+        });
+    }
+
+    fn resolve_exceptions(&mut self) -> Result<(), Error> {
+        // exceptions are only expressible in a context-free grammar if both operands derive
+        // finitely many strings, in which case the difference is spelled out as terminals; the
+        // helpers of other exceptions are worked out on demand, so they resolve in dependency order
+        let exceptions = std::mem::take(&mut self.exceptions);
+        let mut languages = Languages {
+            rules: self.rules.iter().chain(self.helpers.iter()).fold(
+                NoHashMap::<TermKey, Vec<Vec<Term>>>::default(),
+                |mut acc, rule| {
+                    acc.entry(rule.lhs.key)
+                        .or_default()
+                        .extend(rule.rhs.alternatives.iter().map(|terms| terms.to_vec()));
+                    acc
+                },
+            ),
+            exceptions: (exceptions.iter())
+                .map(|exception| (exception.helper.key, exception))
+                .collect(),
+            cache: FxHashMap::default(),
+            visiting: Vec::new(),
+        };
+        for exception in exceptions.iter() {
+            let difference = languages
+                .nonterminal_language(&exception.helper, exception.mark)
+                .map_err(|(mark, message)| self.reader.error_at(mark, &message))?;
+            let difference = (difference.iter())
+                .map(|word| vec![Term::terminal(word)])
+                .collect();
+            self.add_rule(&exception.helper, difference);
+        }
+        Ok(())
+    }
+}
+
+struct Languages<'a> {
+    rules: NoHashMap<TermKey, Vec<Vec<Term>>>,
+    exceptions: NoHashMap<TermKey, &'a Exception>,
+    cache: FxHashMap<TermKey, BTreeSet<String>>,
+    visiting: Vec<TermKey>,
+}
+
+impl Languages<'_> {
+    // errors are reported at the innermost exception whose operands they come from
+    fn language(
+        &mut self,
+        alternatives: &[Vec<Term>],
+        mark: Mark,
+    ) -> Result<BTreeSet<String>, (Mark, String)> {
+        let too_many = || {
+            let message = format!(
+                "exception operands derive more than {} strings",
+                MAX_EXCEPTION_WORDS
+            );
+            (mark, message)
+        };
+        let mut words = BTreeSet::new();
+        for terms in alternatives.iter() {
+            let mut prefixes = BTreeSet::from([String::new()]);
+            for term in terms.iter() {
+                let suffixes = match term.is_terminal() {
+                    true => BTreeSet::from([term.content.clone()]),
+                    false => self.nonterminal_language(term, mark)?,
+                };
+                // the product is counted before it is built
+                if prefixes.len().saturating_mul(suffixes.len()) > MAX_EXCEPTION_WORDS {
+                    return Err(too_many());
+                }
+                prefixes = prefixes
+                    .iter()
+                    .cartesian_product(suffixes.iter())
+                    .map(|(prefix, suffix)| format!("{}{}", prefix, suffix))
+                    .collect();
+            }
+            if words.len().saturating_add(prefixes.len()) > MAX_EXCEPTION_WORDS {
+                return Err(too_many());
+            }
+            words.extend(prefixes);
+        }
+        Ok(words)
+    }
+
+    fn nonterminal_language(
+        &mut self,
+        term: &Term,
+        mark: Mark,
+    ) -> Result<BTreeSet<String>, (Mark, String)> {
+        if let Some(words) = self.cache.get(&term.key) {
+            return Ok(words.clone());
+        }
+        // recursion through the same nonterminal may derive arbitrarily long strings
+        if self.visiting.contains(&term.key) {
+            let message = "exceptions require both operands to derive finitely many strings";
+            return Err((mark, message.to_string()));
+        }
+        self.visiting.push(term.key);
+        let words = match self.exceptions.get(&term.key).copied() {
+            Some(exception) => {
+                let minuend = self.language(&exception.minuend, exception.mark)?;
+                let subtrahend = self.language(&exception.subtrahend, exception.mark)?;
+                let difference = minuend
+                    .difference(&subtrahend)
+                    .cloned()
+                    .collect::<BTreeSet<String>>();
+                if difference.is_empty() {
+                    let message = "exception excludes every string";
+                    return Err((exception.mark, message.to_string()));
+                }
+                difference
+            }
+            None => {
+                let Some(alternatives) = self.rules.get(&term.key).cloned() else {
+                    let message = format!(
+                        "exception operands use undefined nonterminal {:?}",
+                        term.content
+                    );
+                    return Err((mark, message));
+                };
+                self.language(&alternatives, mark)?
+            }
+        };
+        self.visiting.pop();
+        self.cache.insert(term.key, words.clone());
+        Ok(words)
+    }
+}
+
+pub(crate) fn write(grammar: &Grammar) -> Result<String, Error> {
+    if let Some(name) = grammar
+        .rules
+        .iter()
+        .flat_map(|rule| rule.rhs.alternatives.iter().flat_map(|terms| terms.iter()))
+        .chain(grammar.rules.iter().map(|rule| rule.lhs.as_ref()))
+        .filter(|term| term.is_nonterminal())
+        .map(|term| &term.content)
+        .find(|name| !is_meta_identifier(name) && !is_special_sequence(name))
+    {
+        return Err(Error::UnsupportedSyntaxError(format!(
+            "nonterminal {:?} can be written neither as a meta identifier nor as a special \
+             sequence",
+            name
+        )));
+    }
+    let writer = EBNFWriter::new(grammar);
+    Ok(grammar
+        .rules
+        .iter()
+        .filter(|rule| !writer.is_inlined(&rule.lhs))
+        .map(|rule| {
+            format!(
+                "{} ={};",
+                identifier(&rule.lhs.content),
+                writer.definitions_list(
+                    (rule.rhs.alternatives.iter())
+                        .zip(rule.rhs.weights.iter().copied())
+                        .map(|(terms, weight)| (terms.as_slice(), weight))
+                )
+            )
+        })
+        .join("\n"))
+}
+
+struct EBNFWriter<'a> {
+    grammar: &'a Grammar,
+    references: NoHashMap<TermKey, usize>,
+}
+
+impl<'a> EBNFWriter<'a> {
+    fn new(grammar: &'a Grammar) -> Self {
+        // recursive references of a rule to itself do not prevent it from being inlined
+        let mut references = NoHashMap::<TermKey, usize>::default();
+        for rule in grammar.rules.iter() {
+            for terms in rule.rhs.alternatives.iter() {
+                for term in terms
+                    .iter()
+                    .filter(|term| term.is_nonterminal() && term.key != rule.lhs.key)
+                {
+                    *references.entry(term.key).or_default() += 1;
+                }
+            }
+        }
+        Self {
+            grammar,
+            references,
+        }
+    }
+
+    fn is_helper(&self, term: &Term) -> bool {
+        self.grammar.helpers.contains(&term.key)
+    }
+
+    fn is_inlined(&self, term: &Term) -> bool {
+        self.is_helper(term)
+            && term.key != self.grammar.start
+            && self.references.get(&term.key) == Some(&1)
+            && self.grammar.rule_lut.contains_key(&term.key)
+            && self.grammar.rule_lut[&term.key]
+                .rhs
+                .weights
+                .iter()
+                .all(|weight| *weight == 1.0)
+            && self.sugar(term).is_some()
+    }
+
+    fn definitions_list<'b>(
+        &self,
+        alternatives: impl Iterator<Item = (&'b [Term], f64)>,
+    ) -> String {
+        // empty definitions are written as nothing at all between the separators
+        alternatives
+            .map(|(terms, weight)| {
+                let definition = match self.single_definition(terms) {
+                    definition if definition.is_empty() => " ".to_owned(),
+                    definition => format!(" {} ", definition),
+                };
+                match weight == 1.0 {
+                    true => definition,
+                    false => format!("{}@ {} ", definition, weight),
+                }
+            })
+            .join("|")
+    }
+
+    fn single_definition(&self, terms: &[Term]) -> String {
+        terms
+            .iter()
+            .filter(|term| !term.is_epsilon())
+            .map(|term| match term.is_terminal() {
+                true => terminal(&term.content),
+                false if self.is_inlined(term) => self.sugar(term).unwrap(),
+                false => identifier(&term.content),
+            })
+            .join(", ")
+    }
+
+    fn sugar(&self, term: &Term) -> Option<String> {
+        let alternatives = &self.grammar.rule(term.key)?.rhs.alternatives;
+        let refers = |terms: &[Term]| terms.iter().any(|t| t.key == term.key);
+        let (empty, others): (Vec<_>, Vec<_>) = alternatives
+            .iter()
+            .partition(|terms| terms.iter().all(|t| t.is_epsilon()));
+        if !empty.is_empty()
+            && !others.is_empty()
+            && others.iter().all(|terms| {
+                terms.last().map(|t| t.key) == Some(term.key) && !refers(&terms[..terms.len() - 1])
+            })
+        {
+            let body =
+                self.definitions_list(others.iter().map(|terms| (&terms[..terms.len() - 1], 1.0)));
+            return Some(format!("{{{}}}", body));
+        }
+        if alternatives.iter().any(|terms| refers(terms)) {
+            return None;
+        }
+        if !empty.is_empty() && !others.is_empty() {
+            let body = self.definitions_list(others.iter().map(|terms| (terms.as_slice(), 1.0)));
+            return Some(format!("[{}]", body));
+        }
+        let body = self.definitions_list(alternatives.iter().map(|terms| (terms.as_slice(), 1.0)));
+        Some(format!("({})", body))
+    }
+}
+
+fn identifier(name: &str) -> String {
+    match is_meta_identifier(name) {
+        true => name.to_owned(),
+        false => format!("? {} ?", name),
+    }
+}
+
+fn is_meta_identifier(name: &str) -> bool {
+    // single spaces separate the words of a meta identifier, the first of which starts with a
+    // letter, while any further ones only need to start with a letter or digit
+    name.split(' ').enumerate().all(|(i, word)| {
+        word.chars().next().is_some_and(|c| match i {
+            0 => c.is_alphabetic(),
+            _ => c.is_alphanumeric(),
+        }) && word.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+fn is_special_sequence(name: &str) -> bool {
+    !name.contains('?') && !name.is_empty() && name.trim() == name
+}
+
+fn terminal(content: &str) -> String {
+    // ISO terminal strings have no escape sequences, so strings containing both kinds of quotes
+    // are split into a concatenation of several terminal strings
+    if !content.contains('"') {
+        return format!("\"{}\"", content);
+    }
+    if !content.contains('\'') {
+        return format!("'{}'", content);
+    }
+    let (head, tail) = content.split_at(content.find('"').unwrap());
+    match head.is_empty() {
+        true => format!("'\"', {}", terminal(&tail[1..])),
+        false => format!("{}, {}", terminal(head), terminal(tail)),
+    }
+}
@@ -0,0 +1,222 @@
+use crate::analysis;
+use crate::grammar::Grammar;
+use crate::term::Term;
+use crate::transform;
+use crate::types::FxHashMap;
+use crate::types::NoHashMap;
+use crate::types::TermKey;
+use std::collections::btree_set;
+use std::collections::BTreeSet;
+
+pub struct Words {
+    grammar: Grammar,
+    // the words of each length a nonterminal derives, filled in as they are needed
+    words: FxHashMap<(TermKey, usize), BTreeSet<String>>,
+    min_lengths: NoHashMap<TermKey, usize>,
+    // the nonterminals whose words of some length are also words of the same length of the
+    // nonterminal, as the other terms of one of its alternatives can be empty
+    units: NoHashMap<TermKey, Vec<TermKey>>,
+    // the length of the longest word, none if the language is infinite
+    max_length: Option<usize>,
+    length: usize,
+    current: btree_set::IntoIter<String>,
+}
+
+impl Words {
+    pub(crate) fn new(grammar: &Grammar) -> Self {
+        // with every nonterminal productive and reachable, the lengths of the words derived from
+        // any of them are bounded exactly when those of the start symbol are
+        let original = grammar;
+        let mut grammar = transform::remove_useless_symbols(original);
+        // the transformed grammar starts at its first rule, an unchecked one may have no rule for
+        // its start symbol and derive no words at all
+        if original.start_rule().is_err() {
+            grammar.start = original.start;
+        }
+        let min_lengths = analysis::shortest_yields(&grammar)
+            .into_iter()
+            .map(|(key, word)| (key, word.chars().count()))
+            .collect::<NoHashMap<TermKey, usize>>();
+        let mut units = NoHashMap::<TermKey, Vec<TermKey>>::default();
+        for rule in grammar.rules.iter() {
+            for terms in rule.rhs.alternatives.iter() {
+                for (i, term) in terms.iter().enumerate() {
+                    let others_empty = terms
+                        .iter()
+                        .enumerate()
+                        .all(|(j, other)| i == j || min_length(&min_lengths, other) == 0);
+                    if term.is_nonterminal() && others_empty {
+                        units.entry(rule.lhs.key).or_default().push(term.key);
+                    }
+                }
+            }
+        }
+        Self {
+            max_length: longest_word(&grammar),
+            words: Default::default(),
+            min_lengths,
+            units,
+            grammar,
+            length: 0,
+            current: BTreeSet::new().into_iter(),
+        }
+    }
+
+    fn derive(&mut self, nonterminal: TermKey, length: usize) {
+        if self.words.contains_key(&(nonterminal, length)) {
+            return;
+        }
+        let mut scope = vec![nonterminal];
+        let mut i = 0;
+        while i < scope.len() {
+            for unit in self.units.get(&scope[i]).into_iter().flatten() {
+                if !scope.contains(unit) {
+                    scope.push(*unit);
+                }
+            }
+            i += 1;
+        }
+        // shorter words of the terms come first, they cannot depend on the ones of this length
+        for &key in scope.iter() {
+            let Some(rule) = self.grammar.rule(key).cloned() else {
+                continue;
+            };
+            for terms in rule.rhs.alternatives.iter() {
+                let total = terms
+                    .iter()
+                    .map(|term| self.min_length(term))
+                    .sum::<usize>();
+                for term in terms.iter().filter(|term| term.is_nonterminal()) {
+                    let Some(longest) = length.checked_sub(total - self.min_length(term)) else {
+                        continue;
+                    };
+                    for n in self.min_length(term)..length.min(longest + 1) {
+                        self.derive(term.key, n);
+                    }
+                }
+            }
+        }
+        for &key in scope.iter() {
+            self.words.entry((key, length)).or_default();
+        }
+        // words of this length can pass between the nonterminals in scope, through unit or
+        // nullable alternatives, which makes this a fixed point
+        let mut was_updated = true;
+        while was_updated {
+            was_updated = false;
+            for &key in scope.iter() {
+                let Some(rule) = self.grammar.rule(key) else {
+                    continue;
+                };
+                let mut words = BTreeSet::new();
+                for terms in rule.rhs.alternatives.iter() {
+                    self.expand(terms, length, &mut String::new(), &mut words);
+                }
+                let entry = self.words.get_mut(&(key, length)).unwrap();
+                for word in words {
+                    was_updated |= entry.insert(word);
+                }
+            }
+        }
+    }
+
+    fn expand(
+        &self,
+        terms: &[Term],
+        length: usize,
+        prefix: &mut String,
+        words: &mut BTreeSet<String>,
+    ) {
+        let Some((term, rest)) = terms.split_first() else {
+            if length == 0 {
+                words.insert(prefix.clone());
+            }
+            return;
+        };
+        let rest_length = rest.iter().map(|term| self.min_length(term)).sum::<usize>();
+        if term.is_terminal() {
+            let n = term.content.chars().count();
+            if n + rest_length <= length {
+                prefix.push_str(&term.content);
+                self.expand(rest, length - n, prefix, words);
+                prefix.truncate(prefix.len() - term.content.len());
+            }
+            return;
+        }
+        for n in self.min_length(term)..=length.saturating_sub(rest_length) {
+            for word in self.words.get(&(term.key, n)).into_iter().flatten() {
+                prefix.push_str(word);
+                self.expand(rest, length - n, prefix, words);
+                prefix.truncate(prefix.len() - word.len());
+            }
+        }
+    }
+
+    fn min_length(&self, term: &Term) -> usize {
+        min_length(&self.min_lengths, term)
+    }
+}
+
+fn min_length(min_lengths: &NoHashMap<TermKey, usize>, term: &Term) -> usize {
+    match term.is_terminal() {
+        true => term.content.chars().count(),
+        false => min_lengths.get(&term.key).copied().unwrap_or_default(),
+    }
+}
+
+impl Iterator for Words {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(word) = self.current.next() {
+                return Some(word);
+            }
+            if self
+                .max_length
+                .is_some_and(|max_length| self.length > max_length)
+            {
+                return None;
+            }
+            let start = self.grammar.start_rule().ok()?.lhs.key;
+            self.derive(start, self.length);
+            self.current = self.words[&(start, self.length)].clone().into_iter();
+            self.length += 1;
+        }
+    }
+}
+
+fn longest_word(grammar: &Grammar) -> Option<usize> {
+    // the longest word of a finite language has a derivation which never repeats a nonterminal
+    // along a path, so the lengths settle after as many rounds as there are rules, while those of
+    // an infinite language keep growing
+    let mut lengths = NoHashMap::<TermKey, usize>::default();
+    for _ in 0..=grammar.rules.len() {
+        let mut was_updated = false;
+        for rule in grammar.rules.iter() {
+            for terms in rule.rhs.alternatives.iter() {
+                let candidate = terms
+                    .iter()
+                    .map(|term| match term.is_terminal() {
+                        true => Some(term.content.chars().count()),
+                        false => lengths.get(&term.key).copied(),
+                    })
+                    .sum::<Option<usize>>();
+                let Some(candidate) = candidate else {
+                    continue;
+                };
+                if lengths
+                    .get(&rule.lhs.key)
+                    .is_none_or(|&length| candidate > length)
+                {
+                    lengths.insert(rule.lhs.key, candidate);
+                    was_updated = true;
+                }
+            }
+        }
+        if !was_updated {
+            return Some(lengths.get(&grammar.start).copied().unwrap_or_default());
+        }
+    }
+    None
+}
@@ -1,3 +1,6 @@
+use itertools::Itertools;
+use std::fmt;
+
 #[derive(Debug)]
 pub enum Error {
     InvalidGrammarSyntaxError(String),
@@ -5,4 +8,89 @@ pub enum Error {
     NotATerminalError(String),
     InfinitelyRecursiveProductionError(String),
     EmptyGrammarError(String),
+    UndefinedNonterminalError(String),
+    UndefinedStartSymbolError(String),
+    ParserConflictError(String),
+    GenerationBoundsError(String),
+    UnsupportedSyntaxError(String),
+    InvalidWeightError(String),
+    SyntaxError(SyntaxError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub(crate) position: usize,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) found: Option<char>,
+    pub(crate) expected: Vec<char>,
+    pub(crate) expected_end: bool,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(
+        input: &[char],
+        position: usize,
+        expected: Vec<char>,
+        expected_end: bool,
+    ) -> Self {
+        let preceding = &input[..position];
+        let line = preceding.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = preceding.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        Self {
+            position,
+            line,
+            column,
+            found: input.get(position).copied(),
+            expected,
+            expected_end,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn found(&self) -> Option<char> {
+        self.found
+    }
+
+    pub fn expected(&self) -> &[char] {
+        &self.expected
+    }
+
+    pub fn expected_end(&self) -> bool {
+        self.expected_end
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.found {
+            Some(c) => write!(f, "unexpected '{}'", c.escape_default())?,
+            None => write!(f, "unexpected end of input")?,
+        }
+        write!(f, " at line {}, column {}", self.line, self.column)?;
+        let mut expected = self
+            .expected
+            .iter()
+            .map(|c| format!("'{}'", c.escape_default()))
+            .collect::<Vec<String>>();
+        if self.expected_end {
+            expected.push("end of input".to_owned());
+        }
+        match expected.len() {
+            0 => Ok(()),
+            1 => write!(f, ", expected {}", expected[0]),
+            _ => write!(f, ", expected one of {}", expected.iter().join(", ")),
+        }
+    }
 }
@@ -1,106 +1,397 @@
+use crate::alternatives::Alternatives;
+use crate::boltzmann;
+use crate::counter::DerivationCounter;
 use crate::error::Error;
 use crate::grammar::Grammar;
+use crate::term::Term;
+use crate::types::FxHashMap;
+use crate::types::NoHashMap;
 use crate::types::TermKey;
 use itertools::Itertools;
 use rand::rngs::StdRng;
 use rand::seq::IteratorRandom;
 use rand::seq::SliceRandom;
+use std::rc::Rc;
+
+// failed attempts at reaching the minimum length before giving up
+const MAX_ATTEMPTS: usize = 100;
+// boltzmann samples are rejected far more often, but also abandoned as soon as they grow too long
+const MAX_BOLTZMANN_ATTEMPTS: usize = 10000;
+
+#[derive(Debug, Copy, Clone)]
+pub struct GenerationBounds {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub max_steps: usize,
+}
+
+impl Default for GenerationBounds {
+    fn default() -> Self {
+        Self {
+            min_length: 0,
+            max_length: usize::MAX,
+            max_steps: usize::MAX,
+        }
+    }
+}
 
 pub(crate) struct Generator<'a> {
     pub(crate) grammar: &'a Grammar,
     pub(crate) stack: Vec<TermKey>,
     pub(crate) sample: String,
     pub(crate) rng: StdRng,
+    bounds: GenerationBounds,
+    // the fewest characters and expansions any derivation from a nonterminal needs
+    min_lengths: NoHashMap<TermKey, usize>,
+    min_steps: NoHashMap<TermKey, usize>,
+    length: usize,
+    steps: usize,
+    pending_length: usize,
+    pending_steps: usize,
+    // the weights of the alternatives under a tuned boltzmann sampler
+    weights: FxHashMap<*const Vec<Term>, f64>,
 }
 
-impl Generator<'_> {
+impl<'a> Generator<'a> {
+    pub(crate) fn new(grammar: &'a Grammar, rng: StdRng, bounds: GenerationBounds) -> Self {
+        Self {
+            grammar,
+            stack: Vec::new(),
+            sample: String::new(),
+            rng,
+            bounds,
+            min_lengths: min_derivations(grammar, 0, |content| content.chars().count()),
+            min_steps: min_derivations(grammar, 1, |_| 0),
+            length: 0,
+            steps: 0,
+            pending_length: 0,
+            pending_steps: 0,
+            weights: Default::default(),
+        }
+    }
+
     fn init(&mut self) {
+        let start = self.grammar.start;
         self.stack.clear();
-        self.stack.push(self.grammar.start);
+        self.stack.push(start);
+        self.sample.clear();
+        self.length = 0;
+        self.steps = 0;
+        self.pending_length = min_cost(&self.min_lengths, start);
+        self.pending_steps = min_cost(&self.min_steps, start);
     }
 
     fn process_terminals(&mut self) {
         while self
             .stack
             .last()
-            .map(|term_key| self.grammar.term(*term_key).terminal_content().is_ok())
-            .unwrap_or(false)
+            .and_then(|term_key| self.grammar.term(*term_key))
+            .is_some_and(|term| term.terminal_content().is_ok())
         {
-            let term_key = self.stack.pop().unwrap();
-            self.sample.extend(
-                self.grammar
-                    .term(term_key)
-                    .terminal_content()
-                    .unwrap()
-                    .chars()
-                    .rev(),
-            );
+            let term = self
+                .stack
+                .pop()
+                .and_then(|term_key| self.grammar.term(term_key));
+            if let Some(Ok(content)) = term.map(|term| term.terminal_content()) {
+                let length = content.chars().count();
+                self.length += length;
+                self.pending_length = self.pending_length.saturating_sub(length);
+                self.sample.extend(content.chars().rev());
+            }
         }
     }
 
+    fn check_bounds(&self) -> Result<(), Error> {
+        let start = self.grammar.start;
+        let min_length = min_cost(&self.min_lengths, start);
+        let min_steps = min_cost(&self.min_steps, start);
+        if self.bounds.min_length > self.bounds.max_length || min_length > self.bounds.max_length {
+            return Err(Error::GenerationBoundsError(format!(
+                "cannot generate a word of at least {} and at most {} characters, the shortest \
+                 word has {}",
+                self.bounds.min_length, self.bounds.max_length, min_length
+            )));
+        }
+        if min_steps > self.bounds.max_steps {
+            return Err(Error::GenerationBoundsError(format!(
+                "cannot generate a word within {} steps, the shortest derivation takes {}",
+                self.bounds.max_steps, min_steps
+            )));
+        }
+        Ok(())
+    }
+
+    fn candidates(
+        &self,
+        nonterminal: TermKey,
+        alternatives: &'a Alternatives,
+    ) -> Vec<(&'a Rc<Vec<Term>>, f64)> {
+        // keep to the alternatives which can still be completed within the bounds, and while the
+        // word is certain to end up too short, to those which grow it the most
+        let length = (self.length.saturating_add(self.pending_length))
+            .saturating_sub(min_cost(&self.min_lengths, nonterminal));
+        let steps = (self.steps + 1)
+            .saturating_add(self.pending_steps)
+            .saturating_sub(min_cost(&self.min_steps, nonterminal));
+        let feasible = (alternatives.alternatives.iter())
+            .zip(alternatives.weights.iter().copied())
+            .map(|(terms, weight)| (terms, weight, length.saturating_add(self.min_length(terms))))
+            .filter(|(terms, _, length)| {
+                *length <= self.bounds.max_length
+                    && steps.saturating_add(self.min_steps(terms)) <= self.bounds.max_steps
+            })
+            .collect::<Vec<(&Rc<Vec<Term>>, f64, usize)>>();
+        let longest = feasible.iter().map(|(_, _, length)| *length).max();
+        match longest {
+            Some(longest) if longest < self.bounds.min_length => feasible
+                .into_iter()
+                .filter(|(_, _, length)| *length == longest)
+                .map(|(terms, weight, _)| (terms, weight))
+                .collect(),
+            _ => feasible
+                .into_iter()
+                .map(|(terms, weight, _)| (terms, weight))
+                .collect(),
+        }
+    }
+
+    fn expand(&mut self, nonterminal: TermKey, terms: &[Term]) {
+        self.steps += 1;
+        self.pending_length = (self.pending_length)
+            .saturating_sub(min_cost(&self.min_lengths, nonterminal))
+            .saturating_add(self.min_length(terms));
+        self.pending_steps = (self.pending_steps)
+            .saturating_sub(min_cost(&self.min_steps, nonterminal))
+            .saturating_add(self.min_steps(terms));
+        self.stack.extend(terms.iter().map(|term| term.key));
+    }
+
+    fn min_length(&self, terms: &[Term]) -> usize {
+        terms.iter().fold(0, |acc: usize, term| {
+            acc.saturating_add(match term.terminal_content() {
+                Ok(content) => content.chars().count(),
+                Err(_) => min_cost(&self.min_lengths, term.key),
+            })
+        })
+    }
+
+    fn min_steps(&self, terms: &[Term]) -> usize {
+        terms
+            .iter()
+            .filter(|term| term.is_nonterminal())
+            .fold(0, |acc: usize, term| {
+                acc.saturating_add(min_cost(&self.min_steps, term.key))
+            })
+    }
+
     pub fn generate(&mut self, strategy: GenerationStrategy) -> Result<String, Error> {
-        self.init();
-        while !self.stack.is_empty() {
-            strategy.step(self)?;
-            self.process_terminals();
+        if let GenerationStrategy::UniformWordSampling { length } = strategy {
+            if length < self.bounds.min_length || length > self.bounds.max_length {
+                return Err(Error::GenerationBoundsError(format!(
+                    "cannot generate a word of length {} within the length bounds",
+                    length
+                )));
+            }
+            // derivations taking too many steps are rejected, which keeps the others uniform
+            let counter = DerivationCounter::new(self.grammar, length);
+            for _ in 0..MAX_ATTEMPTS {
+                let (word, steps) = counter.sample(length, &mut self.rng)?;
+                if steps <= self.bounds.max_steps {
+                    return Ok(word);
+                }
+            }
+            return Err(Error::GenerationBoundsError(format!(
+                "failed to generate a word of length {} within {} steps in {} attempts",
+                length, self.bounds.max_steps, MAX_ATTEMPTS
+            )));
         }
-        Ok(self.sample.chars().rev().collect())
+        self.check_bounds()?;
+        let (min_length, max_length, attempts) = match strategy {
+            GenerationStrategy::Boltzmann { expected_size } => {
+                // samples are accepted within a fifth of the expected size
+                self.weights = boltzmann::tune(self.grammar, expected_size)?;
+                let tolerance = expected_size / 5;
+                let min_length = self.bounds.min_length.max(expected_size - tolerance);
+                let max_length = self.bounds.max_length.min(expected_size + tolerance);
+                (min_length, max_length, MAX_BOLTZMANN_ATTEMPTS)
+            }
+            _ => (self.bounds.min_length, usize::MAX, MAX_ATTEMPTS),
+        };
+        for _ in 0..attempts {
+            self.init();
+            while !self.stack.is_empty()
+                && self.length.saturating_add(self.pending_length) <= max_length
+            {
+                strategy.step(self)?;
+                self.process_terminals();
+            }
+            if self.stack.is_empty() && (min_length..=max_length).contains(&self.length) {
+                return Ok(self.sample.chars().rev().collect());
+            }
+        }
+        Err(Error::GenerationBoundsError(format!(
+            "failed to generate a word of {} to {} characters in {} attempts",
+            min_length, max_length, attempts
+        )))
+    }
+}
+
+fn choose<'a, 'b>(
+    alternatives: impl Iterator<Item = &'b (&'a Rc<Vec<Term>>, f64)> + Clone,
+    rng: &mut StdRng,
+) -> Option<&'a Rc<Vec<Term>>>
+where
+    'a: 'b,
+{
+    // filtered alternatives of equal weight are drawn from the iterator like the unweighted
+    // strategies always did, which keeps the words generated from a seed the same
+    match is_uniform(alternatives.clone().map(|(_, weight)| *weight)) {
+        true => alternatives.choose(rng).map(|(terms, _)| *terms),
+        false => choose_from_slice(&alternatives.copied().collect::<Vec<_>>(), rng),
     }
 }
 
+fn choose_from_slice<'a>(
+    alternatives: &[(&'a Rc<Vec<Term>>, f64)],
+    rng: &mut StdRng,
+) -> Option<&'a Rc<Vec<Term>>> {
+    // none is chosen if there is no alternative of positive weight
+    let (terms, _) = match is_uniform(alternatives.iter().map(|(_, weight)| *weight)) {
+        true => alternatives.choose(rng)?,
+        false => alternatives
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .ok()?,
+    };
+    Some(terms)
+}
+
+fn is_uniform(mut weights: impl Iterator<Item = f64>) -> bool {
+    weights
+        .next()
+        .is_some_and(|first| first > 0.0 && weights.all(|weight| weight == first))
+}
+
+fn min_cost(costs: &NoHashMap<TermKey, usize>, nonterminal: TermKey) -> usize {
+    *costs.get(&nonterminal).unwrap_or(&usize::MAX)
+}
+
+fn min_derivations(
+    grammar: &Grammar,
+    step_cost: usize,
+    terminal_cost: impl Fn(&str) -> usize,
+) -> NoHashMap<TermKey, usize> {
+    let mut costs = NoHashMap::<TermKey, usize>::default();
+    let mut was_updated = true;
+    while was_updated {
+        was_updated = false;
+        for rule in grammar.rules.iter() {
+            let candidate = rule
+                .rhs
+                .alternatives
+                .iter()
+                .map(|terms| {
+                    terms.iter().fold(step_cost, |acc: usize, term| {
+                        acc.saturating_add(match term.terminal_content() {
+                            Ok(content) => terminal_cost(content),
+                            Err(_) => min_cost(&costs, term.key),
+                        })
+                    })
+                })
+                .min()
+                .unwrap_or(usize::MAX);
+            if candidate < min_cost(&costs, rule.lhs.key) {
+                costs.insert(rule.lhs.key, candidate);
+                was_updated = true;
+            }
+        }
+    }
+    costs
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum GenerationStrategy {
     UniformRHSSampling,
     RecursionAvoidance,
     GreedyTerminals,
+    /// Samples uniformly among the derivations of words of the given length, which samples
+    /// uniformly among the words themselves only if the grammar is unambiguous. Derivations
+    /// taking more than the `max_steps` of the bounds are rejected and sampled again.
+    UniformWordSampling {
+        length: usize,
+    },
+    Boltzmann {
+        expected_size: usize,
+    },
 }
 
 impl GenerationStrategy {
     pub(crate) fn step(&self, context: &mut Generator) -> Result<(), Error> {
         let nonterminal = context.stack.pop().unwrap();
-        let rule = context.grammar.rule(nonterminal);
-        match self {
+        let rule = context.grammar.rule(nonterminal).ok_or_else(|| {
+            let name = context
+                .grammar
+                .term(nonterminal)
+                .map(|term| term.to_string());
+            Error::UndefinedNonterminalError(format!(
+                "cannot generate from undefined nonterminal {}",
+                name.unwrap_or_default()
+            ))
+        })?;
+        let alternatives = context.candidates(nonterminal, &rule.rhs);
+        if alternatives.is_empty() {
+            return Err(Error::GenerationBoundsError(format!(
+                "cannot expand {} within the remaining bounds",
+                rule.lhs
+            )));
+        }
+        let zero_weights = || {
+            Error::GenerationBoundsError(format!(
+                "cannot expand {}, its remaining alternatives all have zero weight",
+                rule.lhs
+            ))
+        };
+        let terms = match self {
             GenerationStrategy::UniformRHSSampling => {
-                let terms = rule
-                    .rhs
-                    .alternatives
-                    .choose(&mut context.rng)
-                    .unwrap()
-                    .iter()
-                    .map(|term| term.key);
-                context.stack.extend(terms);
+                choose_from_slice(&alternatives, &mut context.rng).ok_or_else(zero_weights)?
             }
             GenerationStrategy::RecursionAvoidance => {
-                let terms = match rule
-                    .rhs
-                    .alternatives
-                    .iter()
-                    .filter(|terms| !terms.iter().map(|term| term.key).contains(&rule.lhs.key))
-                    .choose(&mut context.rng)
-                {
-                    Some(terms) => terms.iter().map(|term| term.key),
+                match choose(
+                    alternatives.iter().filter(|(terms, _)| {
+                        !terms.iter().map(|term| term.key).contains(&rule.lhs.key)
+                    }),
+                    &mut context.rng,
+                ) {
+                    Some(terms) => terms,
                     None => {
                         return Err(Error::InfinitelyRecursiveProductionError(
                             "cannot generate from infinitely recursive production rule".to_owned(),
                         ))
                     }
-                };
-                context.stack.extend(terms);
+                }
             }
             GenerationStrategy::GreedyTerminals => {
-                let terms = match rule
-                    .rhs
-                    .alternatives
+                let terminals = alternatives
                     .iter()
-                    .filter(|terms| terms.iter().all(|term| term.terminal_content().is_ok()))
-                    .choose(&mut context.rng)
-                {
-                    Some(terms) => terms,
-                    None => rule.rhs.alternatives.choose(&mut context.rng).unwrap(),
-                };
-                context.stack.extend(terms.iter().map(|term| term.key))
+                    .filter(|(terms, _)| terms.iter().all(|term| term.terminal_content().is_ok()));
+                choose(terminals, &mut context.rng)
+                    .or_else(|| choose_from_slice(&alternatives, &mut context.rng))
+                    .ok_or_else(zero_weights)?
             }
-        }
+            GenerationStrategy::Boltzmann { .. } => {
+                // the tuned weights already account for those of the alternatives
+                let weights = &context.weights;
+                let alternatives = alternatives.iter().map(|(terms, _)| {
+                    let weight = weights.get(&Rc::as_ptr(terms)).copied().unwrap_or_default();
+                    (*terms, weight)
+                });
+                choose_from_slice(&alternatives.collect::<Vec<_>>(), &mut context.rng)
+                    .ok_or_else(zero_weights)?
+            }
+            GenerationStrategy::UniformWordSampling { .. } => {
+                unreachable!("words of a fixed length are sampled from the derivation counts")
+            }
+        };
+        context.expand(nonterminal, terms);
         Ok(())
     }
 }
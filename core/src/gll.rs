@@ -0,0 +1,340 @@
+use crate::backend::Parser;
+use crate::error::Error;
+use crate::error::SyntaxError;
+use crate::grammar::Grammar;
+use crate::sppf::Origins;
+use crate::sppf::SPPFNodeLabel;
+use crate::sppf::SPPF;
+use crate::term::Term;
+use crate::types::FxHashMap;
+use crate::types::FxHashSet;
+use crate::types::TermKey;
+use std::collections::BTreeSet;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+struct Slot {
+    lhs: Rc<Term>,
+    expression: Rc<Vec<Term>>,
+    dot: usize,
+}
+
+impl Slot {
+    fn advance(&self) -> Self {
+        Self {
+            lhs: self.lhs.clone(),
+            expression: self.expression.clone(),
+            dot: self.dot + 1,
+        }
+    }
+}
+
+impl Hash for Slot {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.expression.as_ptr().hash(state);
+        self.dot.hash(state);
+    }
+}
+
+impl PartialEq for Slot {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.expression, &other.expression) && self.dot == other.dot
+    }
+}
+
+impl Eq for Slot {}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+struct Descriptor {
+    slot: Slot,
+    gss: usize,
+    position: usize,
+    node: Option<usize>,
+}
+
+struct GSSNode {
+    // the slot to return to, the root has none
+    slot: Option<Slot>,
+    edges: Vec<(usize, Option<usize>)>,
+    popped: Vec<usize>,
+}
+
+struct Packed {
+    expression: Rc<Vec<Term>>,
+    pivot: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+#[derive(Default)]
+struct GLLState {
+    // forest nodes are interned so descriptors and stack edges can refer to them by index
+    nodes: Vec<(SPPFNodeLabel, Vec<Packed>)>,
+    node_ids: FxHashMap<SPPFNodeLabel, usize>,
+    packed: FxHashSet<(usize, *const Term, usize)>,
+    gss: Vec<GSSNode>,
+    gss_ids: FxHashMap<(Slot, usize), usize>,
+    edges: FxHashSet<(usize, usize, Option<usize>)>,
+    popped: FxHashSet<(usize, usize)>,
+    pending: Vec<Descriptor>,
+    seen: FxHashSet<Descriptor>,
+    furthest: usize,
+    expected: BTreeSet<char>,
+}
+
+pub struct GLLParser {
+    grammar: Grammar,
+    origins: Origins,
+    input: Vec<char>,
+    state: GLLState,
+}
+
+impl GLLParser {
+    fn init_input(&mut self, input: &str) {
+        self.input = input.chars().collect();
+        self.state = GLLState::default();
+        self.state.gss.push(GSSNode {
+            slot: None,
+            edges: Vec::new(),
+            popped: Vec::new(),
+        });
+    }
+
+    fn run(&mut self) {
+        let start = self.grammar.start;
+        self.call(start, 0, 0);
+        while let Some(descriptor) = self.state.pending.pop() {
+            self.step(descriptor);
+        }
+    }
+
+    fn call(&mut self, nonterminal: TermKey, gss: usize, position: usize) {
+        let Some(rule) = self.grammar.rule(nonterminal) else {
+            return;
+        };
+        let lhs = rule.lhs.clone();
+        for expression in rule.rhs.alternatives.clone() {
+            let slot = Slot {
+                lhs: lhs.clone(),
+                expression,
+                dot: 0,
+            };
+            self.add(slot, gss, position, None);
+        }
+    }
+
+    fn add(&mut self, slot: Slot, gss: usize, position: usize, node: Option<usize>) {
+        let descriptor = Descriptor {
+            slot,
+            gss,
+            position,
+            node,
+        };
+        if self.state.seen.insert(descriptor.clone()) {
+            self.state.pending.push(descriptor);
+        }
+    }
+
+    fn step(&mut self, descriptor: Descriptor) {
+        let Descriptor {
+            mut slot,
+            gss,
+            mut position,
+            mut node,
+        } = descriptor;
+        loop {
+            if position > self.state.furthest {
+                self.state.furthest = position;
+                self.state.expected.clear();
+            }
+            let Some(term) = slot.expression.get(slot.dot).cloned() else {
+                break;
+            };
+            if term.is_nonterminal() {
+                let gss = self.create(slot.advance(), gss, position, node);
+                self.call(term.key, gss, position);
+                return;
+            }
+            let symbol = term.content.chars().next();
+            if position == self.state.furthest {
+                self.state.expected.extend(symbol);
+            }
+            if self.input.get(position).copied() != symbol {
+                return;
+            }
+            let right = self.node(SPPFNodeLabel::symbol(&term, position, position + 1));
+            slot = slot.advance();
+            node = Some(self.node_p(&slot, node, right));
+            position += 1;
+        }
+        let node = match node {
+            Some(node) => node,
+            None => {
+                // an empty alternative
+                let label = SPPFNodeLabel::symbol(&slot.lhs, position, position);
+                let node = self.node(label);
+                self.add_packed(node, &slot.expression, position, None, None);
+                node
+            }
+        };
+        self.pop(gss, node);
+    }
+
+    fn create(&mut self, slot: Slot, gss: usize, position: usize, node: Option<usize>) -> usize {
+        let key = (slot.clone(), position);
+        let target = match self.state.gss_ids.get(&key) {
+            Some(&target) => target,
+            None => {
+                let target = self.state.gss.len();
+                self.state.gss.push(GSSNode {
+                    slot: Some(slot.clone()),
+                    edges: Vec::new(),
+                    popped: Vec::new(),
+                });
+                self.state.gss_ids.insert(key, target);
+                target
+            }
+        };
+        if self.state.edges.insert((target, gss, node)) {
+            self.state.gss[target].edges.push((gss, node));
+            // the callee may already have returned, those results are passed along the new edge
+            for right in self.state.gss[target].popped.clone() {
+                let y = self.node_p(&slot, node, right);
+                self.add(slot.clone(), gss, self.state.nodes[right].0.end(), Some(y));
+            }
+        }
+        target
+    }
+
+    fn pop(&mut self, gss: usize, right: usize) {
+        let Some(slot) = self.state.gss[gss].slot.clone() else {
+            return;
+        };
+        if !self.state.popped.insert((gss, right)) {
+            return;
+        }
+        self.state.gss[gss].popped.push(right);
+        let end = self.state.nodes[right].0.end();
+        for (target, node) in self.state.gss[gss].edges.clone() {
+            let y = self.node_p(&slot, node, right);
+            self.add(slot.clone(), target, end, Some(y));
+        }
+    }
+
+    fn node(&mut self, label: SPPFNodeLabel) -> usize {
+        if let Some(&node) = self.state.node_ids.get(&label) {
+            return node;
+        }
+        let node = self.state.nodes.len();
+        self.state.node_ids.insert(label.clone(), node);
+        self.state.nodes.push((label, Vec::new()));
+        node
+    }
+
+    fn node_p(&mut self, slot: &Slot, left: Option<usize>, right: usize) -> usize {
+        // a prefix of length one is represented by the node of its only symbol
+        let is_complete = slot.dot == slot.expression.len();
+        if slot.dot == 1 && !is_complete {
+            return right;
+        }
+        let pivot = self.state.nodes[right].0.start();
+        let end = self.state.nodes[right].0.end();
+        let start = left.map_or(pivot, |left| self.state.nodes[left].0.start());
+        let label = match is_complete {
+            true => SPPFNodeLabel::symbol(&slot.lhs, start, end),
+            false => SPPFNodeLabel::prefix(&slot.expression, slot.dot, start, end),
+        };
+        let node = self.node(label);
+        self.add_packed(node, &slot.expression, pivot, left, Some(right));
+        node
+    }
+
+    fn add_packed(
+        &mut self,
+        node: usize,
+        expression: &Rc<Vec<Term>>,
+        pivot: usize,
+        left: Option<usize>,
+        right: Option<usize>,
+    ) {
+        if self.state.packed.insert((node, expression.as_ptr(), pivot)) {
+            self.state.nodes[node].1.push(Packed {
+                expression: expression.clone(),
+                pivot,
+                left,
+                right,
+            });
+        }
+    }
+
+    fn root(&self, end: usize) -> Option<usize> {
+        let start = &self.grammar.rule(self.grammar.start)?.lhs;
+        let label = SPPFNodeLabel::symbol(start, 0, end);
+        self.state.node_ids.get(&label).copied()
+    }
+
+    fn forest(&self, root: usize) -> SPPF {
+        // only the nodes reachable from the root are kept, descriptors also explore dead ends
+        let mut sppf = SPPF::new(self.state.nodes[root].0.clone());
+        let mut visited = FxHashSet::<usize>::default();
+        let mut pending = vec![root];
+        visited.insert(root);
+        while let Some(node) = pending.pop() {
+            let (label, children) = &self.state.nodes[node];
+            sppf.insert(label.clone());
+            for packed in children.iter() {
+                let left = packed.left.map(|left| self.state.nodes[left].0.clone());
+                let right = packed.right.map(|right| self.state.nodes[right].0.clone());
+                sppf.add_packed(label, &packed.expression, packed.pivot, left, right);
+                for child in packed.left.into_iter().chain(packed.right) {
+                    if visited.insert(child) {
+                        pending.push(child);
+                    }
+                }
+            }
+        }
+        sppf.restore_terminals(&self.origins)
+    }
+}
+
+impl Parser for GLLParser {
+    fn recognize(&mut self, input: &str) -> bool {
+        self.init_input(input);
+        self.run();
+        self.root(self.input.len()).is_some()
+    }
+
+    fn check(&mut self, input: &str) -> Result<(), Error> {
+        self.grammar.start_rule()?;
+        if self.recognize(input) {
+            return Ok(());
+        }
+        let position = self.state.furthest;
+        Err(Error::SyntaxError(SyntaxError::new(
+            &self.input,
+            position,
+            self.state.expected.iter().copied().collect(),
+            self.root(position).is_some(),
+        )))
+    }
+
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        self.check(input)?;
+        Ok(self.forest(self.root(self.input.len()).unwrap()))
+    }
+}
+
+impl From<&Grammar> for GLLParser {
+    fn from(grammar: &Grammar) -> Self {
+        let mut grammar = grammar.clone();
+        let origins = grammar.atomize_terminals();
+        Self {
+            grammar,
+            origins,
+            input: Vec::new(),
+            state: GLLState::default(),
+        }
+    }
+}
@@ -1,17 +1,44 @@
+use crate::abnf;
+use crate::analysis;
+use crate::analysis::GrammarAnalysis;
+use crate::analysis::LL1Conflict;
+use crate::backend::CompiledGrammar;
+use crate::backend::Parser;
+use crate::backend::ParserBackend;
+use crate::bnf;
 use crate::codify::Codify;
+use crate::counter::DerivationCounter;
+use crate::cyk::CYKParser;
+use crate::ebnf;
+use crate::enumerator::Words;
 use crate::error::Error;
+use crate::generator::GenerationBounds;
 use crate::generator::GenerationStrategy;
 use crate::generator::Generator;
+use crate::gll::GLLParser;
+use crate::lr::LRAutomaton;
+use crate::lr::LRKind;
+use crate::lr::LRParser;
 use crate::parser::ExtendedEarleyParser;
+use crate::parser::ParserTable;
+use crate::parser::ParserTableCache;
+use crate::recovery::RecoveredParse;
+use crate::recovery::RecoveryCosts;
 use crate::rule::Rule;
+use crate::sppf::Origins;
+use crate::sppf::SPPF;
 use crate::term::Term;
+use crate::transform;
 use crate::types::NoHashMap;
+use crate::types::NoHashSet;
 use crate::types::TermKey;
 use itertools::Itertools;
+use num_bigint::BigUint;
 use rand::rngs::StdRng;
 use rand::SeedableRng;
 use std::fmt;
 use std::rc::Rc;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Grammar {
@@ -19,6 +46,10 @@ pub struct Grammar {
     pub(crate) rules: Vec<Rule>,
     pub(crate) rule_lut: NoHashMap<TermKey, Rule>,
     pub(crate) term_lut: NoHashMap<TermKey, Term>,
+    // nonterminals the readers introduced for the sugar of their notations
+    pub(crate) helpers: NoHashSet<TermKey>,
+    // compiled the first time the grammar parses an input, shared by all its earley parsers
+    pub(crate) table: ParserTableCache,
 }
 
 impl Grammar {
@@ -26,8 +57,96 @@ impl Grammar {
         GrammarBuilder::default()
     }
 
+    pub fn parse_bnf(text: &str) -> Result<Self, Error> {
+        bnf::parse(text)
+    }
+
+    pub fn parse_abnf(text: &str) -> Result<Self, Error> {
+        abnf::parse(text)
+    }
+
+    pub fn parse_ebnf(text: &str) -> Result<Self, Error> {
+        ebnf::parse(text)
+    }
+
+    pub fn to_ebnf(&self) -> Result<String, Error> {
+        ebnf::write(self)
+    }
+
+    pub fn analysis(&self, k: usize) -> GrammarAnalysis<'_> {
+        GrammarAnalysis::new(self, k)
+    }
+
+    pub fn ll1_conflicts(&self) -> Vec<LL1Conflict<'_>> {
+        self.analysis(1).ll1_conflicts()
+    }
+
+    pub fn lr_automaton(&self, kind: LRKind) -> Result<LRAutomaton, Error> {
+        LRAutomaton::new(self, kind)
+    }
+
+    pub fn lr_parser(&self, kind: LRKind) -> Result<LRParser, Error> {
+        LRParser::try_from(self.lr_automaton(kind)?)
+    }
+
+    pub fn unproductive_nonterminals(&self) -> Vec<&Term> {
+        let productive = analysis::productive_nonterminals(self);
+        self.rules
+            .iter()
+            .map(|rule| rule.lhs.as_ref())
+            .filter(|lhs| !productive.contains(&lhs.key))
+            .collect()
+    }
+
+    pub fn unreachable_nonterminals(&self) -> Vec<&Term> {
+        let reachable = analysis::reachable_nonterminals(self);
+        self.rules
+            .iter()
+            .map(|rule| rule.lhs.as_ref())
+            .filter(|lhs| !reachable.contains(&lhs.key))
+            .collect()
+    }
+
+    pub fn remove_useless_symbols(&self) -> Grammar {
+        transform::remove_useless_symbols(self)
+    }
+
+    pub fn left_recursion(&self) -> Vec<Vec<&Term>> {
+        analysis::left_recursion_cycles(self)
+            .into_iter()
+            .map(|cycle| {
+                let rules = cycle.into_iter().filter_map(|key| self.rule(key));
+                rules.map(|rule| rule.lhs.as_ref()).collect()
+            })
+            .collect()
+    }
+
+    pub fn remove_left_recursion(&self) -> Grammar {
+        transform::remove_left_recursion(self)
+    }
+
+    pub fn remove_epsilon_rules(&self) -> Grammar {
+        transform::remove_epsilon(self)
+    }
+
+    pub fn remove_unit_productions(&self) -> Grammar {
+        transform::remove_unit_productions(self)
+    }
+
+    pub fn lift_terminals(&self) -> Grammar {
+        transform::lift_terminals(self)
+    }
+
+    pub fn to_chomsky_normal_form(&self) -> Grammar {
+        transform::to_chomsky_normal_form(self)
+    }
+
+    pub fn to_greibach_normal_form(&self) -> Grammar {
+        transform::to_greibach_normal_form(self)
+    }
+
     pub fn generate(&self) -> Result<String, Error> {
-        self.generation_interface(None, None)
+        self.generation_interface(None, None, GenerationBounds::default())
     }
 
     pub fn generate_parameterized(
@@ -35,44 +154,128 @@ impl Grammar {
         strategy: GenerationStrategy,
         seed: u64,
     ) -> Result<String, Error> {
-        self.generation_interface(Some(strategy), Some(seed))
+        self.generation_interface(Some(strategy), Some(seed), GenerationBounds::default())
+    }
+
+    pub fn generate_bounded(&self, bounds: GenerationBounds) -> Result<String, Error> {
+        self.generation_interface(None, None, bounds)
+    }
+
+    pub fn generate_bounded_parameterized(
+        &self,
+        strategy: GenerationStrategy,
+        seed: u64,
+        bounds: GenerationBounds,
+    ) -> Result<String, Error> {
+        self.generation_interface(Some(strategy), Some(seed), bounds)
     }
 
     fn generation_interface(
         &self,
         strategy: Option<GenerationStrategy>,
         seed: Option<u64>,
+        bounds: GenerationBounds,
     ) -> Result<String, Error> {
-        let mut generator = Generator {
-            grammar: self,
-            stack: Vec::new(),
-            sample: String::new(),
-            rng: match seed {
-                Some(seed) => StdRng::seed_from_u64(seed),
-                None => StdRng::from_entropy(),
-            },
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
         };
+        let mut generator = Generator::new(self, rng, bounds);
         generator.generate(strategy.unwrap_or(GenerationStrategy::UniformRHSSampling))
     }
 
-    pub fn parse(&self, input: &str) {
-        // TODO
+    pub fn count_derivations(&self, length: usize) -> Option<BigUint> {
+        DerivationCounter::new(self, length).count_derivations(length)
+    }
+
+    pub fn enumerate(&self) -> Words {
+        Words::new(self)
+    }
+
+    pub fn parser(&self, backend: ParserBackend) -> Result<Box<dyn Parser>, Error> {
+        match backend {
+            ParserBackend::Earley => Ok(Box::new(ExtendedEarleyParser::from(self))),
+            ParserBackend::CYK => Ok(Box::new(CYKParser::from(self))),
+            ParserBackend::GLL => Ok(Box::new(GLLParser::from(self))),
+            ParserBackend::LR(kind) => Ok(Box::new(self.lr_parser(kind)?)),
+        }
+    }
+
+    pub fn compile(&self) -> CompiledGrammar {
+        CompiledGrammar::from(self)
+    }
+
+    pub fn parse(&self, input: &str) -> Result<SPPF, Error> {
+        self.compile().parse(input)
     }
 
     pub fn recognize(&self, input: &str) -> bool {
+        self.compile().recognize(input)
+    }
+
+    pub fn parse_recovering(&self, input: &str) -> Result<RecoveredParse, Error> {
+        self.parse_recovering_parameterized(input, RecoveryCosts::default())
+    }
+
+    pub fn parse_recovering_parameterized(
+        &self,
+        input: &str,
+        costs: RecoveryCosts,
+    ) -> Result<RecoveredParse, Error> {
         let mut parser = ExtendedEarleyParser::from(self);
-        parser.recognize(input)
+        parser.parse_recovering(input, costs)
     }
 
-    pub(crate) fn rule(&self, term_key: TermKey) -> &Rule {
-        self.rule_lut.get(&term_key).unwrap()
+    pub fn check(&self, input: &str) -> Result<(), Error> {
+        self.compile().check(input)
+    }
+
+    pub(crate) fn rule(&self, term_key: TermKey) -> Option<&Rule> {
+        self.rule_lut.get(&term_key)
+    }
+
+    pub(crate) fn term(&self, term_key: TermKey) -> Option<&Term> {
+        self.term_lut.get(&term_key)
+    }
+
+    pub(crate) fn start_rule(&self) -> Result<&Rule, Error> {
+        // the unchecked builder lets a grammar through whose start symbol has no rule
+        if self.rules.is_empty() {
+            return Err(Error::EmptyGrammarError(
+                "a grammar requires at least one production rule".to_owned(),
+            ));
+        }
+        self.rule(self.start).ok_or_else(|| {
+            Error::UndefinedStartSymbolError(match self.term(self.start) {
+                Some(start) => format!("start symbol {} has no production rule", start),
+                None => "start symbol has no production rule".to_owned(),
+            })
+        })
     }
 
-    pub(crate) fn term(&self, term_key: TermKey) -> &Term {
-        self.term_lut.get(&term_key).unwrap()
+    pub(crate) fn table(&self) -> Rc<ParserTable> {
+        self.table.get_or_init(self)
     }
 
-    pub(crate) fn atomize_terminals(&mut self) {
+    pub(crate) fn from_rules(rules: Vec<Rule>) -> Grammar {
+        // transformations keep the order of the rules they produce, the first rule is the start
+        let start = rules
+            .first()
+            .map_or_else(unnamed_start, |rule| rule.lhs.as_ref().clone());
+        let mut grammar = Grammar {
+            start: start.key,
+            rules,
+            rule_lut: Default::default(),
+            term_lut: Default::default(),
+            helpers: Default::default(),
+            table: Default::default(),
+        };
+        grammar.init();
+        grammar
+    }
+
+    pub(crate) fn atomize_terminals(&mut self) -> Origins {
+        let mut origins = Origins::default();
         for rule in self.rules.iter_mut() {
             for alternative in rule.rhs.alternatives.iter_mut() {
                 let mut tmp = Vec::new();
@@ -83,10 +286,12 @@ impl Grammar {
                         tmp.append(&mut term.atomize().unwrap());
                     }
                 }
-                *alternative = Rc::new(tmp);
+                let original = std::mem::replace(alternative, Rc::new(tmp));
+                origins.insert(alternative, &original);
             }
         }
         self.init();
+        origins
     }
 
     fn build_rule_lut(&mut self) {
@@ -110,6 +315,7 @@ impl Grammar {
     }
 
     fn init(&mut self) {
+        self.table = Default::default();
         self.build_rule_lut();
         self.build_term_lut();
     }
@@ -120,19 +326,29 @@ impl fmt::Display for Grammar {
         write!(
             f,
             "{}",
-            self.rules
-                .iter()
-                .map(|rule| rule.to_string())
-                .intersperse("\n".into())
-                .collect::<String>()
+            self.rules.iter().map(|rule| rule.to_string()).join("\n")
         )
     }
 }
 
+impl FromStr for Grammar {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_bnf(s)
+    }
+}
+
 impl Codify for Grammar {
     fn codify(&self, prefix: &str) -> String {
+        let (helpers, rules) = (self.rules.iter())
+            .partition::<Vec<&Rule>, _>(|rule| self.helpers.contains(&rule.lhs.key));
         let mut s = format!("{prefix}Grammar::builder().rules(&[\n");
-        for rule in self.rules.iter() {
+        for rule in rules {
+            s.push_str(&format!("{},\n", rule.codify(prefix)));
+        }
+        s.push_str("]).helpers(&[\n");
+        for rule in helpers {
             s.push_str(&format!("{},\n", rule.codify(prefix)));
         }
         s.push_str("]).build()");
@@ -140,9 +356,15 @@ impl Codify for Grammar {
     }
 }
 
+fn unnamed_start() -> Term {
+    Term::nonterminal("")
+}
+
 #[derive(Default)]
 pub struct GrammarBuilder {
     rules: Vec<Rule>,
+    helpers: NoHashSet<TermKey>,
+    start: Option<Term>,
 }
 
 impl GrammarBuilder {
@@ -156,30 +378,93 @@ impl GrammarBuilder {
         self
     }
 
+    // rules the readers generated themselves, written back as the sugar they stand for
+    pub fn helpers(mut self, rules: &[Rule]) -> Self {
+        self.helpers.extend(rules.iter().map(|rule| rule.lhs.key));
+        self.rules.extend_from_slice(rules);
+        self
+    }
+
+    pub fn start(mut self, start: &str) -> Self {
+        self.start = Some(Term::nonterminal(start));
+        self
+    }
+
     pub fn build(mut self) -> Grammar {
-        self.collapse();
-        let mut grammar = Grammar {
-            start: self
-                .rules
-                .first()
-                .expect(
-                    "cannot find starting symbol, a grammar requires at least one production rule",
+        // an empty grammar is let through like an undefined start symbol, its parsers report it
+        let start = self.start_symbol().unwrap_or_else(unnamed_start);
+        self.collapse(&start);
+        self.assemble(&start)
+    }
+
+    pub fn try_build(mut self) -> Result<Grammar, Error> {
+        if self.rules.is_empty() {
+            return Err(Error::EmptyGrammarError(
+                "a grammar requires at least one production rule".to_owned(),
+            ));
+        }
+        let start = self.start_symbol().unwrap();
+        self.collapse(&start);
+        let grammar = self.assemble(&start);
+        if grammar.rule(start.key).is_none() {
+            return Err(Error::UndefinedStartSymbolError(format!(
+                "start symbol {} has no production rule",
+                start
+            )));
+        }
+        // every undefined nonterminal is reported together with the rules referring to it
+        let undefined = grammar
+            .rules
+            .iter()
+            .flat_map(|rule| {
+                rule.rhs
+                    .alternatives
+                    .iter()
+                    .flat_map(|terms| terms.iter())
+                    .filter(|term| term.is_nonterminal() && grammar.rule(term.key).is_none())
+                    .map(move |term| (term.to_string(), rule.lhs.to_string()))
+            })
+            .sorted()
+            .dedup()
+            .chunk_by(|(term, _)| term.clone())
+            .into_iter()
+            .map(|(term, references)| {
+                format!(
+                    "{} referenced by {}",
+                    term,
+                    references.map(|(_, rule)| rule).join(", ")
                 )
-                .lhs
-                .key,
+            })
+            .collect::<Vec<String>>();
+        if !undefined.is_empty() {
+            return Err(Error::UndefinedNonterminalError(format!(
+                "undefined nonterminals: {}",
+                undefined.join("; ")
+            )));
+        }
+        Ok(grammar)
+    }
+
+    fn start_symbol(&self) -> Option<Term> {
+        self.start
+            .clone()
+            .or_else(|| self.rules.first().map(|rule| rule.lhs.as_ref().clone()))
+    }
+
+    fn assemble(self, start: &Term) -> Grammar {
+        let mut grammar = Grammar {
+            start: start.key,
             rule_lut: Default::default(),
             term_lut: Default::default(),
+            helpers: self.helpers,
+            table: Default::default(),
             rules: self.rules,
         };
         grammar.init();
         grammar
     }
 
-    fn collapse(&mut self) {
-        let start = match self.rules.first() {
-            Some(rule) => rule.clone(),
-            None => return,
-        };
+    fn collapse(&mut self, start: &Term) {
         self.rules.sort_unstable_by_key(|rule| rule.lhs.to_string());
         self.rules = self.rules.iter().fold(
             Vec::<Rule>::with_capacity(self.rules.len()),
@@ -196,10 +481,11 @@ impl GrammarBuilder {
                 acc
             },
         );
-        let idx = self
+        if let Ok(idx) = self
             .rules
-            .binary_search_by_key(&start.lhs.to_string(), |rule| rule.lhs.to_string())
-            .unwrap();
-        self.rules.swap(0, idx);
+            .binary_search_by_key(&start.to_string(), |rule| rule.lhs.to_string())
+        {
+            self.rules.swap(0, idx);
+        }
     }
 }
@@ -1,18 +1,42 @@
+mod abnf;
 mod alternatives;
+mod analysis;
+mod backend;
+mod bnf;
+mod boltzmann;
 mod codify;
+mod counter;
+mod cyk;
+mod ebnf;
+mod enumerator;
 mod error;
 mod generator;
+mod gll;
 mod grammar;
+mod lr;
 mod parser;
+mod reader;
+mod recovery;
 mod rule;
 mod sppf;
 mod term;
+mod transform;
+mod tree;
 mod types;
 pub use alternatives::Alternatives;
+pub use analysis::{GrammarAnalysis, LL1Conflict, LL1ConflictKind};
+pub use backend::{CompiledGrammar, Parser, ParserBackend};
 pub use codify::Codify;
-pub use error::Error;
-pub use generator::GenerationStrategy;
+pub use cyk::CYKParser;
+pub use enumerator::Words;
+pub use error::{Error, SyntaxError};
+pub use generator::{GenerationBounds, GenerationStrategy};
+pub use gll::GLLParser;
 pub use grammar::{Grammar, GrammarBuilder};
+pub use lr::{LRAutomaton, LRConflict, LRConflictKind, LRKind, LRParser};
 pub use parser::ExtendedEarleyParser;
+pub use recovery::{RecoveredError, RecoveredParse, RecoveryCosts, Repair};
 pub use rule::Rule;
+pub use sppf::{SPPFNode, SPPFNodeItem, SPPFNodeLabel, SPPFPackedNode, SPPF};
 pub use term::Term;
+pub use tree::{ParseTree, ParseTrees};
@@ -0,0 +1,550 @@
+use crate::alternatives::Alternatives;
+use crate::analysis;
+use crate::analysis::GrammarAnalysis;
+use crate::backend::Parser;
+use crate::error::Error;
+use crate::error::SyntaxError;
+use crate::grammar::Grammar;
+use crate::rule::Rule;
+use crate::sppf::Origins;
+use crate::sppf::SPPF;
+use crate::term::Term;
+use crate::tree::ParseTree;
+use crate::types::FxHashMap;
+use crate::types::FxHashSet;
+use crate::types::NoHashMap;
+use crate::types::TermKey;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LRKind {
+    LR0,
+    LALR1,
+    LR1,
+}
+
+// lookaheads are single characters like the atomized terminals, none stands for the end of input
+type Lookahead = Option<char>;
+type Item = (usize, usize);
+type ItemSet = BTreeMap<Item, BTreeSet<Lookahead>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Shift(usize),
+    Reduce(usize),
+}
+
+struct Production {
+    lhs: Rc<Term>,
+    expression: Rc<Vec<Term>>,
+}
+
+struct LRState {
+    kernel: ItemSet,
+    items: ItemSet,
+    transitions: Vec<(Term, usize)>,
+    parent: Option<(usize, Term)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LRConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LRConflict {
+    pub(crate) kind: LRConflictKind,
+    pub(crate) state: usize,
+    pub(crate) lookaheads: Vec<Option<char>>,
+    pub(crate) reductions: Vec<Rule>,
+    pub(crate) prefix: Vec<Term>,
+    pub(crate) example: Option<String>,
+}
+
+impl LRConflict {
+    pub fn kind(&self) -> LRConflictKind {
+        self.kind
+    }
+
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    // none stands for the end of the input
+    pub fn lookaheads(&self) -> &[Option<char>] {
+        &self.lookaheads
+    }
+
+    pub fn reductions(&self) -> &[Rule] {
+        &self.reductions
+    }
+
+    // the symbols on the stack when the conflict is hit
+    pub fn prefix(&self) -> &[Term] {
+        &self.prefix
+    }
+
+    // shortest input driving the parser into the conflicting state, if the prefix derives any
+    pub fn example(&self) -> Option<&str> {
+        self.example.as_deref()
+    }
+}
+
+impl fmt::Display for LRConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            LRConflictKind::ShiftReduce => "shift/reduce",
+            LRConflictKind::ReduceReduce => "reduce/reduce",
+        };
+        let mut lookaheads = self.lookaheads.iter().map(|lookahead| match lookahead {
+            Some(c) => format!("'{}'", c.escape_default()),
+            None => "end of input".to_owned(),
+        });
+        let prefix = match self.prefix.is_empty() {
+            true => "\"\"".to_owned(),
+            false => self.prefix.iter().join(" "),
+        };
+        write!(
+            f,
+            "{} conflict in state {} on {} after {}",
+            kind,
+            self.state,
+            lookaheads.join(", "),
+            prefix
+        )?;
+        if let Some(example) = &self.example {
+            write!(f, " (e.g. \"{}\")", example.escape_default())?;
+        }
+        let mut actions = self
+            .reductions
+            .iter()
+            .map(|rule| format!("reduce {}", rule.to_string().trim_end()))
+            .collect::<Vec<String>>();
+        if self.kind == LRConflictKind::ShiftReduce {
+            actions.insert(0, "shift".to_owned());
+        }
+        write!(f, ": {}", actions.join(" or "))
+    }
+}
+
+pub struct LRAutomaton {
+    kind: LRKind,
+    productions: Vec<Production>,
+    states: Vec<LRState>,
+    actions: Vec<BTreeMap<Lookahead, Vec<Action>>>,
+    gotos: Vec<NoHashMap<TermKey, usize>>,
+    conflicts: Vec<LRConflict>,
+    origins: Origins,
+}
+
+impl LRAutomaton {
+    pub(crate) fn new(grammar: &Grammar, kind: LRKind) -> Result<Self, Error> {
+        let mut grammar = grammar.clone();
+        let origins = grammar.atomize_terminals();
+        let start = grammar.start_rule()?.lhs.clone();
+        let mut builder = LRBuilder::new(&grammar, kind, &start);
+        builder.build();
+        let mut automaton = Self {
+            kind,
+            productions: builder.productions,
+            states: builder.states,
+            actions: Vec::new(),
+            gotos: Vec::new(),
+            conflicts: Vec::new(),
+            origins,
+        };
+        automaton.build_tables(&grammar);
+        automaton.build_conflicts(&grammar);
+        Ok(automaton)
+    }
+
+    pub fn kind(&self) -> LRKind {
+        self.kind
+    }
+
+    pub fn n_states(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn conflicts(&self) -> &[LRConflict] {
+        &self.conflicts
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    fn build_tables(&mut self, grammar: &Grammar) {
+        // without lookaheads an LR(0) automaton reduces regardless of the next symbol
+        let alphabet = grammar
+            .term_lut
+            .values()
+            .filter_map(|term| term.content.chars().next().filter(|_| term.is_terminal()))
+            .map(Some)
+            .chain([None])
+            .collect::<BTreeSet<Lookahead>>();
+        for state in self.states.iter() {
+            let mut actions = BTreeMap::<Lookahead, Vec<Action>>::new();
+            let mut gotos = NoHashMap::default();
+            for (term, target) in state.transitions.iter() {
+                match term.is_terminal() {
+                    true => actions
+                        .entry(term.content.chars().next())
+                        .or_default()
+                        .push(Action::Shift(*target)),
+                    false => {
+                        gotos.insert(term.key, *target);
+                    }
+                }
+            }
+            for (&(production, dot), lookaheads) in state.items.iter() {
+                if dot < self.productions[production].expression.len() {
+                    continue;
+                }
+                let lookaheads = match (self.kind, production) {
+                    (_, 0) => BTreeSet::from([None]),
+                    (LRKind::LR0, _) => alphabet.clone(),
+                    _ => lookaheads.clone(),
+                };
+                for lookahead in lookaheads {
+                    let entry = actions.entry(lookahead).or_default();
+                    entry.push(Action::Reduce(production));
+                }
+            }
+            self.actions.push(actions);
+            self.gotos.push(gotos);
+        }
+    }
+
+    fn build_conflicts(&mut self, grammar: &Grammar) {
+        // lookaheads leading to the same set of conflicting actions are reported together
+        let yields = analysis::shortest_yields(grammar);
+        for (state, actions) in self.actions.iter().enumerate() {
+            let mut groups = Vec::<(LRConflictKind, Vec<usize>, Vec<Lookahead>)>::new();
+            for (lookahead, actions) in actions.iter().filter(|(_, actions)| actions.len() > 1) {
+                let reductions = actions
+                    .iter()
+                    .filter_map(|action| match action {
+                        Action::Reduce(production) => Some(*production),
+                        Action::Shift(_) => None,
+                    })
+                    .collect::<Vec<usize>>();
+                let kind = match reductions.len() < actions.len() {
+                    true => LRConflictKind::ShiftReduce,
+                    false => LRConflictKind::ReduceReduce,
+                };
+                match groups
+                    .iter_mut()
+                    .find(|(other, productions, _)| *other == kind && *productions == reductions)
+                {
+                    Some((_, _, lookaheads)) => lookaheads.push(*lookahead),
+                    None => groups.push((kind, reductions, vec![*lookahead])),
+                }
+            }
+            if groups.is_empty() {
+                continue;
+            }
+            let prefix = self.prefix(state);
+            let example = prefix
+                .iter()
+                .map(|term| match term.is_terminal() {
+                    true => Some(term.content.clone()),
+                    false => yields.get(&term.key).cloned(),
+                })
+                .collect::<Option<String>>();
+            for (kind, reductions, lookaheads) in groups {
+                let reductions = reductions
+                    .into_iter()
+                    .map(|production| {
+                        let production = &self.productions[production];
+                        Rule {
+                            lhs: production.lhs.clone(),
+                            rhs: Alternatives::from(vec![production.expression.to_vec()]),
+                        }
+                    })
+                    .collect();
+                self.conflicts.push(LRConflict {
+                    kind,
+                    state,
+                    lookaheads,
+                    reductions,
+                    prefix: prefix.clone(),
+                    example: example.clone(),
+                });
+            }
+        }
+    }
+
+    fn prefix(&self, mut state: usize) -> Vec<Term> {
+        let mut prefix = Vec::new();
+        while let Some((parent, term)) = &self.states[state].parent {
+            prefix.push(term.clone());
+            state = *parent;
+        }
+        prefix.reverse();
+        prefix
+    }
+}
+
+struct LRBuilder<'a> {
+    kind: LRKind,
+    productions: Vec<Production>,
+    rule_productions: NoHashMap<TermKey, Vec<usize>>,
+    // FIRST sets of every suffix of every production, with whether the suffix is nullable
+    firsts: Vec<Vec<(BTreeSet<Lookahead>, bool)>>,
+    states: Vec<LRState>,
+    lookup: FxHashMap<ItemSet, usize>,
+    analysis: GrammarAnalysis<'a>,
+}
+
+impl<'a> LRBuilder<'a> {
+    fn new(grammar: &'a Grammar, kind: LRKind, start: &Term) -> Self {
+        // the augmented start production gets a helper name which collides with no other symbol
+        let names = grammar
+            .term_lut
+            .values()
+            .chain(grammar.rules.iter().map(|rule| rule.lhs.as_ref()))
+            .map(|term| term.content.as_str())
+            .collect::<FxHashSet<&str>>();
+        let augmented = (1..)
+            .map(|n| format!("{}.start{}", start.content, n))
+            .find(|name| !names.contains(name.as_str()))
+            .unwrap();
+        let mut productions = vec![Production {
+            lhs: Rc::new(Term::nonterminal(&augmented)),
+            expression: Rc::new(vec![start.clone()]),
+        }];
+        let mut rule_productions = NoHashMap::<TermKey, Vec<usize>>::default();
+        for rule in grammar.rules.iter() {
+            for expression in rule.rhs.alternatives.iter() {
+                rule_productions
+                    .entry(rule.lhs.key)
+                    .or_default()
+                    .push(productions.len());
+                productions.push(Production {
+                    lhs: rule.lhs.clone(),
+                    expression: expression.clone(),
+                });
+            }
+        }
+        let mut builder = Self {
+            kind,
+            productions,
+            rule_productions,
+            firsts: Vec::new(),
+            states: Vec::new(),
+            lookup: Default::default(),
+            analysis: GrammarAnalysis::new(grammar, 1),
+        };
+        builder.firsts = builder
+            .productions
+            .iter()
+            .map(|production| {
+                (0..=production.expression.len())
+                    .map(|dot| builder.first(&production.expression[dot..]))
+                    .collect()
+            })
+            .collect();
+        builder
+    }
+
+    fn first(&self, terms: &[Term]) -> (BTreeSet<Lookahead>, bool) {
+        let mut first = self.analysis.first_of(terms);
+        let nullable = first.remove("");
+        let lookaheads = first.iter().map(|terminal| terminal.chars().next());
+        (lookaheads.collect(), nullable)
+    }
+
+    fn build(&mut self) {
+        let lookaheads = match self.kind {
+            LRKind::LR0 => BTreeSet::new(),
+            _ => BTreeSet::from([None]),
+        };
+        self.add_state(ItemSet::from([((0, 0), lookaheads)]), None);
+        let mut pending = VecDeque::from([0]);
+        while let Some(index) = pending.pop_front() {
+            let items = self.closure(&self.states[index].kernel);
+            let mut successors = Vec::<(Term, ItemSet)>::new();
+            for (&(production, dot), lookaheads) in items.iter() {
+                let Some(term) = self.productions[production].expression.get(dot) else {
+                    continue;
+                };
+                let position = match successors.iter().position(|(other, _)| other == term) {
+                    Some(position) => position,
+                    None => {
+                        successors.push((term.clone(), ItemSet::new()));
+                        successors.len() - 1
+                    }
+                };
+                let kernel = &mut successors[position].1;
+                kernel
+                    .entry((production, dot + 1))
+                    .or_default()
+                    .extend(lookaheads.iter().copied());
+            }
+            let mut transitions = Vec::with_capacity(successors.len());
+            for (term, kernel) in successors {
+                let (target, is_updated) = self.add_state(kernel, Some((index, term.clone())));
+                if is_updated {
+                    pending.push_back(target);
+                }
+                transitions.push((term, target));
+            }
+            let state = &mut self.states[index];
+            state.items = items;
+            state.transitions = transitions;
+        }
+    }
+
+    fn add_state(&mut self, kernel: ItemSet, parent: Option<(usize, Term)>) -> (usize, bool) {
+        // canonical LR(1) states are told apart by their lookaheads, LALR(1) states only by their
+        // items and merge the lookaheads of every kernel reaching them
+        let key = match self.kind {
+            LRKind::LR1 => kernel.clone(),
+            _ => kernel.keys().map(|&item| (item, BTreeSet::new())).collect(),
+        };
+        if let Some(&index) = self.lookup.get(&key) {
+            let mut is_updated = false;
+            for (item, lookaheads) in kernel {
+                let existing = self.states[index].kernel.entry(item).or_default();
+                let size = existing.len();
+                existing.extend(lookaheads);
+                is_updated |= existing.len() > size;
+            }
+            return (index, is_updated);
+        }
+        self.lookup.insert(key, self.states.len());
+        self.states.push(LRState {
+            kernel,
+            items: ItemSet::new(),
+            transitions: Vec::new(),
+            parent,
+        });
+        (self.states.len() - 1, true)
+    }
+
+    fn closure(&self, kernel: &ItemSet) -> ItemSet {
+        let mut items = kernel.clone();
+        let mut pending = kernel.keys().copied().collect::<Vec<Item>>();
+        while let Some((production, dot)) = pending.pop() {
+            let term = match self.productions[production].expression.get(dot) {
+                Some(term) if term.is_nonterminal() => term,
+                _ => continue,
+            };
+            let mut lookaheads = BTreeSet::new();
+            if self.kind != LRKind::LR0 {
+                let (first, nullable) = &self.firsts[production][dot + 1];
+                lookaheads.extend(first.iter().copied());
+                if *nullable {
+                    lookaheads.extend(items[&(production, dot)].iter().copied());
+                }
+            }
+            for &next in self.rule_productions.get(&term.key).into_iter().flatten() {
+                let is_new = !items.contains_key(&(next, 0));
+                let existing = items.entry((next, 0)).or_default();
+                let size = existing.len();
+                existing.extend(lookaheads.iter().copied());
+                if is_new || existing.len() > size {
+                    pending.push((next, 0));
+                }
+            }
+        }
+        items
+    }
+}
+
+pub struct LRParser {
+    automaton: LRAutomaton,
+}
+
+impl LRParser {
+    fn derive(&self, input: &str) -> Result<ParseTree, Error> {
+        let input = input.chars().collect::<Vec<char>>();
+        let automaton = &self.automaton;
+        let mut states = vec![0];
+        let mut trees = Vec::<ParseTree>::new();
+        let mut position = 0;
+        loop {
+            let state = *states.last().unwrap();
+            let lookahead = input.get(position).copied();
+            let action = automaton.actions[state]
+                .get(&lookahead)
+                .and_then(|actions| actions.first());
+            match action {
+                Some(Action::Shift(target)) => {
+                    trees.push(ParseTree {
+                        term: Term::terminal(&input[position].to_string()),
+                        expression: None,
+                        start: position,
+                        end: position + 1,
+                        children: Vec::new(),
+                    });
+                    states.push(*target);
+                    position += 1;
+                }
+                Some(Action::Reduce(0)) => return Ok(trees.pop().unwrap()),
+                Some(Action::Reduce(production)) => {
+                    let production = &automaton.productions[*production];
+                    let children = trees.split_off(trees.len() - production.expression.len());
+                    states.truncate(states.len() - production.expression.len());
+                    let start = children.first().map_or(position, |child| child.start);
+                    trees.push(ParseTree {
+                        term: production.lhs.as_ref().clone(),
+                        expression: Some(production.expression.clone()),
+                        start,
+                        end: position,
+                        children,
+                    });
+                    let state = *states.last().unwrap();
+                    states.push(automaton.gotos[state][&production.lhs.key]);
+                }
+                None => {
+                    let actions = &automaton.actions[state];
+                    return Err(Error::SyntaxError(SyntaxError::new(
+                        &input,
+                        position,
+                        actions.keys().filter_map(|lookahead| *lookahead).collect(),
+                        actions.contains_key(&None),
+                    )));
+                }
+            }
+        }
+    }
+}
+
+impl Parser for LRParser {
+    fn recognize(&mut self, input: &str) -> bool {
+        self.derive(input).is_ok()
+    }
+
+    fn check(&mut self, input: &str) -> Result<(), Error> {
+        self.derive(input).map(|_| ())
+    }
+
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        self.derive(input)
+            .map(|tree| SPPF::from_tree(&tree).restore_terminals(&self.automaton.origins))
+    }
+}
+
+impl TryFrom<LRAutomaton> for LRParser {
+    type Error = Error;
+
+    fn try_from(automaton: LRAutomaton) -> Result<Self, Self::Error> {
+        match automaton.conflicts.first() {
+            None => Ok(Self { automaton }),
+            Some(conflict) => Err(Error::ParserConflictError(format!(
+                "grammar has {} conflicts in its {:?} automaton, the first is a {}",
+                automaton.conflicts.len(),
+                automaton.kind,
+                conflict
+            ))),
+        }
+    }
+}
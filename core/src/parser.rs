@@ -1,12 +1,24 @@
+use crate::analysis;
+use crate::backend::Parser;
 use crate::error::Error;
+use crate::error::SyntaxError;
 use crate::grammar::Grammar;
+use crate::recovery::RecoveredError;
+use crate::recovery::RecoveredParse;
+use crate::recovery::RecoveryCosts;
+use crate::recovery::Repair;
+use crate::sppf::Origins;
+use crate::sppf::SPPF;
 use crate::term::Term;
 use crate::term::TermKind;
+use crate::types::FxHashMap;
 use crate::types::NoHashMap;
 use crate::types::NoHashSet;
 use crate::types::StateKey;
 use crate::types::TermKey;
 use rustc_hash::FxHasher;
+use std::cell::OnceCell;
+use std::collections::BTreeSet;
 use std::fmt;
 use std::hash::BuildHasher;
 use std::hash::BuildHasherDefault;
@@ -23,7 +35,7 @@ pub(crate) struct EarleyState {
 }
 
 impl EarleyState {
-    fn new(lhs: TermKey, expression: Rc<Vec<Term>>, dot: usize, start: usize) -> Self {
+    pub(crate) fn new(lhs: TermKey, expression: Rc<Vec<Term>>, dot: usize, start: usize) -> Self {
         Self {
             lhs,
             expression,
@@ -32,7 +44,7 @@ impl EarleyState {
         }
     }
 
-    fn advance(&self) -> Self {
+    pub(crate) fn advance(&self) -> Self {
         Self {
             lhs: self.lhs,
             expression: self.expression.clone(),
@@ -71,10 +83,11 @@ impl fmt::Display for EarleyState {
     }
 }
 
-struct Column {
-    symbol: char,
-    states: Vec<EarleyState>,
+pub(crate) struct Column {
+    pub(crate) symbol: char,
+    pub(crate) states: Vec<EarleyState>,
     unique: NoHashSet<StateKey>,
+    waiting: NoHashMap<TermKey, Vec<usize>>,
     transitive: NoHashMap<StateKey, EarleyState>,
     hash_builder: BuildHasherDefault<FxHasher>,
 }
@@ -85,27 +98,45 @@ impl Column {
             symbol,
             states: Vec::new(),
             unique: Default::default(),
+            waiting: Default::default(),
             transitive: Default::default(),
             hash_builder: Default::default(),
         }
     }
 
-    fn add_transitive(&mut self, state: &EarleyState) {
+    fn add_transitive(&mut self, symbol: TermKey, state: &EarleyState) {
         self.transitive
-            .entry(state.lhs)
+            .entry(symbol)
             .or_insert_with(|| state.clone());
     }
 
     fn add(&mut self, state: EarleyState) {
-        let mut hasher = self.hash_builder.build_hasher();
-        state.hash(&mut hasher);
-        let hash = hasher.finish();
-        if !self.unique.contains(&hash) {
-            self.unique.insert(hash);
+        let hash = self.hash_builder.hash_one(&state);
+        if self.unique.insert(hash) {
+            // states are indexed by the nonterminal after their dot, which is what completion and
+            // deterministic reductions look up
+            if let Some(term) = state.at_dot().filter(|term| term.is_nonterminal()) {
+                self.waiting
+                    .entry(term.key)
+                    .or_default()
+                    .push(self.states.len());
+            }
             self.states.push(state);
         }
     }
 
+    pub(crate) fn waiting(&self, symbol: TermKey) -> impl Iterator<Item = &EarleyState> {
+        self.waiting
+            .get(&symbol)
+            .into_iter()
+            .flatten()
+            .map(|&state_index| &self.states[state_index])
+    }
+
+    pub(crate) fn contains(&self, state: &EarleyState) -> bool {
+        self.unique.contains(&self.hash_builder.hash_one(state))
+    }
+
     fn is_empty(&self) -> bool {
         self.states.is_empty()
     }
@@ -115,53 +146,109 @@ impl Column {
     }
 }
 
-struct ParsingContext {
-    grammar: Grammar,
-    input: Vec<char>,
+pub(crate) struct ParserTable {
+    // everything derived from the grammar alone, shared by the parsers compiled from it
+    pub(crate) source: Grammar,
+    pub(crate) grammar: Grammar,
+    pub(crate) origins: Origins,
     nullable: NoHashSet<TermKey>,
+    pub(crate) min_yield: NoHashMap<TermKey, usize>,
 }
 
-impl From<&Grammar> for ParsingContext {
+impl From<&Grammar> for ParserTable {
     fn from(grammar: &Grammar) -> Self {
-        let mut instance = Self {
-            grammar: grammar.clone(),
-            input: Default::default(),
-            nullable: Default::default(),
-        };
-        instance.init();
-        instance
+        let source = grammar.clone();
+        let mut grammar = grammar.clone();
+        let origins = grammar.atomize_terminals();
+        Self {
+            source,
+            origins,
+            nullable: analysis::nullable_nonterminals(&grammar),
+            min_yield: compute_min_yield(&grammar),
+            grammar,
+        }
     }
 }
 
-impl ParsingContext {
-    fn init(&mut self) {
-        self.grammar.atomize_terminals();
-        self.compute_nullable_nonterminals();
+#[derive(Default)]
+pub(crate) struct ParserTableCache(OnceCell<Rc<ParserTable>>);
+
+impl ParserTableCache {
+    pub(crate) fn get_or_init(&self, grammar: &Grammar) -> Rc<ParserTable> {
+        self.0
+            .get_or_init(|| Rc::new(ParserTable::from(grammar)))
+            .clone()
     }
+}
 
-    fn compute_nullable_nonterminals(&mut self) {
-        let mut was_updated = true;
-        while was_updated {
-            was_updated = false;
-            for rule in self.grammar.rules.iter() {
-                if !self.nullable.contains(&rule.lhs.key)
-                    && rule.rhs.alternatives.iter().any(|terms| {
-                        terms
-                            .iter()
-                            .all(|term| term.is_epsilon() || self.nullable.contains(&term.key))
-                    })
-                {
-                    self.nullable.insert(rule.lhs.key);
-                    was_updated = true;
-                }
+// the cache is derived from the rules alone, copies start out empty as they may be changed
+impl Clone for ParserTableCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+// grammars compare by their rules, the cache is derived data left out of equality on purpose
+impl PartialEq for ParserTableCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for ParserTableCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ParserTableCache")
+    }
+}
+
+pub(crate) struct ParsingContext {
+    pub(crate) table: Rc<ParserTable>,
+    pub(crate) input: Vec<char>,
+}
+
+impl From<Rc<ParserTable>> for ParsingContext {
+    fn from(table: Rc<ParserTable>) -> Self {
+        Self {
+            table,
+            input: Default::default(),
+        }
+    }
+}
+
+fn compute_min_yield(grammar: &Grammar) -> NoHashMap<TermKey, usize> {
+    let mut min_yield = NoHashMap::<TermKey, usize>::default();
+    let mut was_updated = true;
+    while was_updated {
+        was_updated = false;
+        for rule in grammar.rules.iter() {
+            let candidate = rule
+                .rhs
+                .alternatives
+                .iter()
+                .map(|terms| remaining_yield(&min_yield, terms))
+                .min()
+                .unwrap_or(usize::MAX);
+            if candidate < *min_yield.get(&rule.lhs.key).unwrap_or(&usize::MAX) {
+                min_yield.insert(rule.lhs.key, candidate);
+                was_updated = true;
             }
         }
     }
+    min_yield
+}
+
+fn remaining_yield(min_yield: &NoHashMap<TermKey, usize>, terms: &[Term]) -> usize {
+    terms.iter().fold(0, |acc: usize, term| match term.kind {
+        TermKind::Terminal => acc.saturating_add(term.content.chars().count()),
+        TermKind::Nonterminal => {
+            acc.saturating_add(*min_yield.get(&term.key).unwrap_or(&usize::MAX))
+        }
+    })
 }
 
 #[derive(Default)]
 pub(crate) struct ParsingState {
-    state_table: Vec<Column>,
+    pub(crate) state_table: Vec<Column>,
 }
 
 impl ParsingState {
@@ -180,11 +267,14 @@ impl ParsingState {
         state_index: usize,
         nonterminal: TermKey,
     ) {
-        for alternative in &context.grammar.rule(nonterminal).rhs.alternatives {
-            let new_state = EarleyState::new(nonterminal, alternative.clone(), 0, col);
-            self.insert(col, new_state);
+        // undefined nonterminals derive nothing and therefore predict no states
+        if let Some(rule) = context.table.grammar.rule(nonterminal) {
+            for alternative in &rule.rhs.alternatives {
+                let new_state = EarleyState::new(nonterminal, alternative.clone(), 0, col);
+                self.insert(col, new_state);
+            }
         }
-        if context.nullable.contains(&nonterminal) {
+        if context.table.nullable.contains(&nonterminal) {
             let new_state = self.get(col, state_index).advance();
             self.insert(col, new_state);
         }
@@ -199,19 +289,20 @@ impl ParsingState {
 
     fn leo_complete(&mut self, col: usize, state_index: usize) {
         let state = self.get(col, state_index).clone();
-        if let Some(topmost) = self.deterministic_reduction(&state) {
-            self.insert(col, topmost);
-        } else {
-            self.earley_complete(col, state_index);
+        // transitive items may only be derived from columns that are already complete
+        if state.start < col {
+            if let Some(topmost) = self.deterministic_reduction(&state) {
+                self.insert(col, topmost);
+                return;
+            }
         }
+        self.earley_complete(col, state_index);
     }
 
     fn earley_complete(&mut self, col: usize, state_index: usize) {
         let state = self.get(col, state_index);
         let completions = self.state_table[state.start]
-            .states
-            .iter()
-            .filter(|s| s.at_dot().is_some() && s.at_dot().unwrap().key == state.lhs)
+            .waiting(state.lhs)
             .map(|parent| parent.advance())
             .collect::<Vec<EarleyState>>();
         for new_state in completions {
@@ -220,36 +311,127 @@ impl ParsingState {
     }
 
     fn deterministic_reduction(&mut self, state: &EarleyState) -> Option<EarleyState> {
-        if let Some(matching_parent) = self.unique_postdot(state) {
-            if let Some(transitive_state) = self.state_table[state.start]
-                .transitive
-                .get(&matching_parent.lhs)
+        // follow the chain of deterministic reductions upwards, remembering each (column, symbol)
+        // pair on the way, since all of them share the same topmost transitive item
+        let mut chain = Vec::new();
+        let mut current = state.clone();
+        let topmost = loop {
+            if let Some(transitive_state) =
+                self.state_table[current.start].transitive.get(&current.lhs)
             {
-                return Some(transitive_state.clone());
+                break transitive_state.clone();
+            }
+            if chain.contains(&(current.start, current.lhs)) {
+                break current;
             }
-            let candidate = matching_parent.advance();
-            let topmost = self.deterministic_reduction(&candidate).or(Some(candidate));
-            self.state_table[matching_parent.start].add_transitive(topmost.as_ref().unwrap());
-            return topmost;
+            match self.unique_postdot(&current) {
+                Some(parent) => {
+                    chain.push((current.start, current.lhs));
+                    current = parent.advance();
+                }
+                None if chain.is_empty() => return None,
+                None => break current,
+            }
+        };
+        for (col, symbol) in chain {
+            self.state_table[col].add_transitive(symbol, &topmost);
         }
-        None
+        Some(topmost)
+    }
+
+    fn unique_postdot(&self, state: &EarleyState) -> Option<EarleyState> {
+        let column = &self.state_table[state.start];
+        let &[state_index] = column.waiting.get(&state.lhs)?.as_slice() else {
+            return None;
+        };
+        let parent = &column.states[state_index];
+        (parent.dot == parent.expression.len() - 1).then(|| parent.clone())
     }
 
-    fn unique_postdot(&mut self, state: &EarleyState) -> Option<EarleyState> {
-        let parents = self.state_table[state.start]
+    pub(crate) fn completed(&self, col: usize) -> Vec<EarleyState> {
+        // deterministic reductions leave only the topmost item of each chain in the chart, the
+        // intermediate completed items are restored per column, and only for the columns a
+        // forest actually reaches
+        let column = &self.state_table[col];
+        let mut completed = column
             .states
             .iter()
-            .filter(|s| s.at_dot().is_some() && s.at_dot().unwrap().key == state.lhs)
-            .take(2)
-            .collect::<Vec<&EarleyState>>();
-        if parents.len() != 1 {
-            return None;
+            .filter(|state| state.at_dot().is_none())
+            .cloned()
+            .collect::<Vec<EarleyState>>();
+        let mut restored = NoHashSet::<StateKey>::default();
+        let mut state_index = 0;
+        while state_index < completed.len() {
+            let state = &completed[state_index];
+            state_index += 1;
+            if state.start == col {
+                continue;
+            }
+            let Some(candidate) = self.unique_postdot(state).map(|parent| parent.advance()) else {
+                continue;
+            };
+            if !column.contains(&candidate)
+                && restored.insert(column.hash_builder.hash_one(&candidate))
+            {
+                completed.push(candidate);
+            }
         }
-        let parent = *parents.first().unwrap();
-        if parent.dot == parent.expression.len() - 1 {
-            return Some(parent.clone());
+        completed
+    }
+
+    fn expected(&self, col: usize) -> BTreeSet<char> {
+        self.state_table[col]
+            .states
+            .iter()
+            .filter_map(|state| state.at_dot())
+            .filter(|term| term.is_terminal())
+            .filter_map(|term| term.content.chars().next())
+            .collect()
+    }
+
+    fn rescan(&mut self, col: usize) {
+        for state_index in 0..self.state_table[col].len() {
+            let symbol = match self.get(col, state_index).at_dot() {
+                Some(term) if term.is_terminal() => term.content.chars().next(),
+                _ => continue,
+            };
+            self.scan(col + 1, state_index, symbol);
+        }
+    }
+
+    fn completion_costs(
+        &self,
+        context: &ParsingContext,
+        min_yield: &NoHashMap<TermKey, usize>,
+    ) -> FxHashMap<(usize, TermKey), usize> {
+        // cost of the cheapest way to finish the parse once a nonterminal starting at a given
+        // column has been completed, relaxed until it reaches a fixed point
+        let mut costs = FxHashMap::<(usize, TermKey), usize>::default();
+        costs.insert((0, context.table.grammar.start), 0);
+        let mut was_updated = true;
+        while was_updated {
+            was_updated = false;
+            for (col, column) in self.state_table.iter().enumerate() {
+                for state in column.states.iter() {
+                    let symbol = match state.at_dot() {
+                        Some(term) if term.is_nonterminal() => term.key,
+                        _ => continue,
+                    };
+                    let parent = match costs.get(&(state.start, state.lhs)) {
+                        Some(cost) => *cost,
+                        None => continue,
+                    };
+                    let candidate = remaining_yield(min_yield, &state.expression[state.dot + 1..])
+                        .saturating_add(parent);
+                    let cost = costs.entry((col, symbol)).or_insert(usize::MAX);
+                    if candidate < *cost {
+                        *cost = candidate;
+                        was_updated = true;
+                    }
+                }
+            }
         }
-        None
+        costs
     }
 
     fn chart_parse(&mut self, context: &ParsingContext) {
@@ -312,7 +494,8 @@ impl ExtendedEarleyParser {
     }
 
     fn init_state_table(&mut self) {
-        self.state.state_table = Vec::with_capacity(self.context.input.len() + 1);
+        self.state.state_table.clear();
+        self.state.state_table.reserve(self.context.input.len() + 1);
         self.state.state_table.push(Column::new('_'));
         for symbol in &self.context.input {
             self.state.state_table.push(Column::new(*symbol));
@@ -320,15 +503,10 @@ impl ExtendedEarleyParser {
     }
 
     fn seed_state_table(&mut self) {
-        let initial_rule = self
-            .context
-            .grammar
-            .rule_lut
-            .get(&self.context.grammar.start)
-            .ok_or(Error::EmptyGrammarError(
-                "cannot seed earley state table without initial rule".to_owned(),
-            ))
-            .unwrap();
+        // without a rule for the start symbol nothing is seeded and every input is rejected
+        let Ok(initial_rule) = self.context.table.grammar.start_rule() else {
+            return;
+        };
         for alternative in &initial_rule.rhs.alternatives {
             self.state.state_table[0].add(EarleyState::new(
                 initial_rule.lhs.key,
@@ -367,28 +545,211 @@ impl ExtendedEarleyParser {
         self.state.chart_parse(&self.context);
     }
 
-    pub fn recognize(&mut self, input: &str) -> bool {
-        self.init_input(input);
-        self.chart_parse();
-        self.state
+    fn accepts(&self, col: usize) -> bool {
+        self.state.state_table[col].states.iter().any(|state| {
+            state.start == 0
+                && state.at_dot().is_none()
+                && state.lhs == self.context.table.grammar.start
+        })
+    }
+
+    fn syntax_error(&self) -> SyntaxError {
+        let position = self
+            .state
             .state_table
-            .last()
-            .unwrap()
+            .iter()
+            .rposition(|column| !column.is_empty())
+            .unwrap_or(0);
+        SyntaxError::new(
+            &self.context.input,
+            position,
+            self.state.expected(position).into_iter().collect(),
+            self.accepts(position),
+        )
+    }
+
+    fn is_stuck(&self, col: usize) -> bool {
+        match self.state.state_table.get(col + 1) {
+            Some(column) => column.is_empty(),
+            None => !self.accepts(col),
+        }
+    }
+
+    fn insert_symbol(&mut self, col: usize, symbol: char) {
+        self.context.input.insert(col, symbol);
+        self.state.state_table.insert(col + 1, Column::new(symbol));
+        self.state.rescan(col);
+        self.state.chart_parse_column_step(&self.context, col + 1);
+    }
+
+    fn try_insert_symbol(&mut self, col: usize, symbol: char) -> bool {
+        self.insert_symbol(col, symbol);
+        if self.is_stuck(col + 1) {
+            self.context.input.remove(col);
+            self.state.state_table.remove(col + 1);
+            return false;
+        }
+        true
+    }
+
+    fn delete_symbols(&mut self, col: usize, n_symbols: usize) -> String {
+        self.state.state_table.drain(col + 1..col + 1 + n_symbols);
+        let deleted = self.context.input.drain(col..col + n_symbols).collect();
+        if col + 1 < self.state.state_table.len() {
+            self.state.rescan(col);
+        }
+        deleted
+    }
+
+    fn completion_symbol(&self, col: usize) -> Option<char> {
+        let min_yield = &self.context.table.min_yield;
+        let costs = self.state.completion_costs(&self.context, min_yield);
+        self.state.state_table[col]
             .states
             .iter()
-            .any(|state| {
-                state.start == 0
-                    && state.at_dot().is_none()
-                    && state.lhs == self.context.grammar.start
+            .filter(|state| state.at_dot().is_some_and(|term| term.is_terminal()))
+            .filter_map(|state| {
+                let parent = costs.get(&(state.start, state.lhs))?;
+                let cost = remaining_yield(min_yield, &state.expression[state.dot..])
+                    .saturating_add(*parent);
+                let symbol = state.at_dot()?.content.chars().next()?;
+                (cost < usize::MAX).then_some((cost, symbol))
             })
+            .min()
+            .map(|(_, symbol)| symbol)
+    }
+
+    fn repair(&mut self, col: usize, costs: &RecoveryCosts) -> Option<(Repair, usize)> {
+        let expected = self.state.expected(col);
+        let n_skipped = (col + 1..=self.context.input.len())
+            .find(|&next| match self.context.input.get(next) {
+                Some(symbol) => expected.contains(symbol),
+                None => self.accepts(col),
+            })
+            .map(|next| next - col);
+        let deletion_cost = n_skipped.map(|n_skipped| n_skipped.saturating_mul(costs.deletion));
+        if deletion_cost.is_none_or(|cost| costs.insertion <= cost) {
+            for &symbol in expected.iter() {
+                if self.try_insert_symbol(col, symbol) {
+                    return Some((Repair::Insertion(symbol), costs.insertion));
+                }
+            }
+        }
+        if let (Some(n_skipped), Some(cost)) = (n_skipped, deletion_cost) {
+            return Some((Repair::Deletion(self.delete_symbols(col, n_skipped)), cost));
+        }
+        // no single edit lets parsing continue, so fall back to discarding the offending symbol,
+        // or, at the end of the input, to inserting along the cheapest completion of the parse
+        if col < self.context.input.len() {
+            return Some((
+                Repair::Deletion(self.delete_symbols(col, 1)),
+                costs.deletion,
+            ));
+        }
+        let symbol = self.completion_symbol(col)?;
+        self.insert_symbol(col, symbol);
+        Some((Repair::Insertion(symbol), costs.insertion))
+    }
+
+    pub fn parse_recovering(
+        &mut self,
+        input: &str,
+        costs: RecoveryCosts,
+    ) -> Result<RecoveredParse, Error> {
+        self.context.table.grammar.start_rule()?;
+        self.init_input(input);
+        let original = self.context.input.clone();
+        let mut errors = Vec::new();
+        let mut spent = 0usize;
+        let mut n_inserted = 0;
+        let mut n_deleted = 0;
+        let mut col = 0;
+        self.state.chart_parse_column_step(&self.context, col);
+        loop {
+            if !self.is_stuck(col) {
+                if col == self.context.input.len() {
+                    break;
+                }
+                col += 1;
+                self.state.chart_parse_column_step(&self.context, col);
+                continue;
+            }
+            let error = SyntaxError::new(
+                &original,
+                col + n_deleted - n_inserted,
+                self.state.expected(col).into_iter().collect(),
+                self.accepts(col),
+            );
+            let (repair, cost) = match self.repair(col, &costs) {
+                Some(repair) => repair,
+                None => return Err(Error::SyntaxError(error)),
+            };
+            spent = spent.saturating_add(cost);
+            if spent > costs.max_cost {
+                return Err(Error::SyntaxError(error));
+            }
+            match &repair {
+                Repair::Insertion(_) => {
+                    n_inserted += 1;
+                    col += 1;
+                }
+                Repair::Deletion(deleted) => n_deleted += deleted.chars().count(),
+            }
+            errors.push(RecoveredError { error, repair });
+        }
+        Ok(RecoveredParse {
+            forest: SPPF::from_chart(&self.context, &self.state),
+            errors,
+            input: self.context.input.iter().collect(),
+        })
+    }
+
+    // the earley parser keeps its methods without the trait in scope
+    pub fn recognize(&mut self, input: &str) -> bool {
+        Parser::recognize(self, input)
+    }
+
+    pub fn check(&mut self, input: &str) -> Result<(), Error> {
+        Parser::check(self, input)
+    }
+
+    pub fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        Parser::parse(self, input)
     }
 }
 
-impl From<&Grammar> for ExtendedEarleyParser {
-    fn from(grammar: &Grammar) -> Self {
+impl Parser for ExtendedEarleyParser {
+    fn recognize(&mut self, input: &str) -> bool {
+        self.init_input(input);
+        self.chart_parse();
+        self.accepts(self.context.input.len())
+    }
+
+    fn check(&mut self, input: &str) -> Result<(), Error> {
+        self.context.table.grammar.start_rule()?;
+        match self.recognize(input) {
+            true => Ok(()),
+            false => Err(Error::SyntaxError(self.syntax_error())),
+        }
+    }
+
+    fn parse(&mut self, input: &str) -> Result<SPPF, Error> {
+        self.check(input)?;
+        Ok(SPPF::from_chart(&self.context, &self.state))
+    }
+}
+
+impl From<Rc<ParserTable>> for ExtendedEarleyParser {
+    fn from(table: Rc<ParserTable>) -> Self {
         Self {
-            context: grammar.into(),
+            context: table.into(),
             state: Default::default(),
         }
     }
 }
+
+impl From<&Grammar> for ExtendedEarleyParser {
+    fn from(grammar: &Grammar) -> Self {
+        grammar.table().into()
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::Error;
+
+#[derive(Clone, Copy)]
+pub(crate) struct Mark {
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Mark {
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+}
+
+pub(crate) struct Reader {
+    chars: Vec<char>,
+    mark: Mark,
+}
+
+impl Reader {
+    pub(crate) fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            mark: Mark {
+                position: 0,
+                line: 1,
+                column: 1,
+            },
+        }
+    }
+
+    pub(crate) fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    pub(crate) fn reset(&mut self, mark: Mark) {
+        self.mark = mark;
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.mark.position >= self.chars.len()
+    }
+
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.peek_nth(0)
+    }
+
+    pub(crate) fn peek_nth(&self, n: usize) -> Option<char> {
+        self.chars.get(self.mark.position + n).copied()
+    }
+
+    pub(crate) fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.mark.position += 1;
+        match c {
+            '\n' => {
+                self.mark.line += 1;
+                self.mark.column = 1;
+            }
+            _ => self.mark.column += 1,
+        }
+        Some(c)
+    }
+
+    pub(crate) fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    pub(crate) fn eat_str(&mut self, s: &str) -> bool {
+        if s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_nth(i) == Some(c))
+        {
+            s.chars().for_each(|_| {
+                self.next();
+            });
+            return true;
+        }
+        false
+    }
+
+    pub(crate) fn expect_str(&mut self, s: &str) -> Result<(), Error> {
+        match self.eat_str(s) {
+            true => Ok(()),
+            false => Err(self.unexpected(&format!("'{}'", s))),
+        }
+    }
+
+    pub(crate) fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek().filter(|c| predicate(*c)) {
+            s.push(c);
+            self.next();
+        }
+        s
+    }
+
+    pub(crate) fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    pub(crate) fn weight(&mut self) -> Result<f64, Error> {
+        // alternatives may end in their weight for generation, those without one weigh one
+        if !self.eat('@') {
+            return Ok(1.0);
+        }
+        self.take_while(|c| c == ' ' || c == '\t');
+        let start = self.mark;
+        let digits = self.take_while(|c| c.is_ascii_digit() || c == '.');
+        match digits.parse::<f64>() {
+            Ok(weight) if weight.is_finite() => Ok(weight),
+            _ if digits.is_empty() => Err(self.unexpected("a weight")),
+            _ => Err(self.error_at(start, "invalid weight")),
+        }
+    }
+
+    pub(crate) fn error(&self, message: &str) -> Error {
+        self.error_at(self.mark, message)
+    }
+
+    pub(crate) fn error_at(&self, mark: Mark, message: &str) -> Error {
+        Error::InvalidGrammarSyntaxError(format!(
+            "line {}, column {}: {}",
+            mark.line, mark.column, message
+        ))
+    }
+
+    pub(crate) fn unsupported_at(&self, mark: Mark, message: &str) -> Error {
+        Error::UnsupportedSyntaxError(format!(
+            "line {}, column {}: {}",
+            mark.line, mark.column, message
+        ))
+    }
+
+    pub(crate) fn unexpected(&self, expected: &str) -> Error {
+        match self.peek() {
+            Some(c) => self.error(&format!(
+                "expected {} but found '{}'",
+                expected,
+                c.escape_default()
+            )),
+            None => self.error(&format!("expected {} but reached end of input", expected)),
+        }
+    }
+
+    pub(crate) fn escaped_literal(&mut self, quote: char) -> Result<String, Error> {
+        let start = self.mark;
+        self.next();
+        let mut content = String::new();
+        loop {
+            match self.next() {
+                Some(c) if c == quote => return Ok(content),
+                Some('\\') => content.push(self.escape_sequence()?),
+                Some('\n') | None => {
+                    return Err(self.error_at(start, "unterminated string literal"));
+                }
+                Some(c) => content.push(c),
+            }
+        }
+    }
+
+    fn escape_sequence(&mut self) -> Result<char, Error> {
+        let start = self.mark;
+        match self.next() {
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('0') => Ok('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => Ok(c),
+            Some('u') if self.eat('{') => {
+                let digits = self.take_while(|c| c.is_ascii_hexdigit());
+                if !self.eat('}') {
+                    return Err(self.unexpected("'}'"));
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error_at(start, "invalid unicode escape sequence"))
+            }
+            _ => Err(self.error_at(start, "invalid escape sequence")),
+        }
+    }
+}
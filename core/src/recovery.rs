@@ -0,0 +1,66 @@
+use crate::error::SyntaxError;
+use crate::sppf::SPPF;
+
+#[derive(Debug, Copy, Clone)]
+pub struct RecoveryCosts {
+    pub insertion: usize,
+    pub deletion: usize,
+    pub max_cost: usize,
+}
+
+impl Default for RecoveryCosts {
+    fn default() -> Self {
+        Self {
+            insertion: 1,
+            deletion: 1,
+            max_cost: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    Insertion(char),
+    Deletion(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredError {
+    pub(crate) error: SyntaxError,
+    pub(crate) repair: Repair,
+}
+
+impl RecoveredError {
+    pub fn error(&self) -> &SyntaxError {
+        &self.error
+    }
+
+    pub fn repair(&self) -> &Repair {
+        &self.repair
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoveredParse {
+    pub(crate) forest: SPPF,
+    pub(crate) errors: Vec<RecoveredError>,
+    pub(crate) input: String,
+}
+
+impl RecoveredParse {
+    pub fn forest(&self) -> &SPPF {
+        &self.forest
+    }
+
+    pub fn errors(&self) -> &[RecoveredError] {
+        &self.errors
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
@@ -1,31 +1,37 @@
 use crate::parser::EarleyState;
+use crate::parser::ParsingContext;
+use crate::parser::ParsingState;
 use crate::term::Term;
+use crate::term::TermKind;
+use crate::tree::ParseTree;
+use crate::tree::ParseTrees;
 use crate::types::FxHashMap;
 use crate::types::FxHashSet;
+use crate::types::NoHashMap;
 use crate::types::TermKey;
+use num_bigint::BigUint;
+use std::fmt;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::rc::Rc;
 
-#[derive(Clone)]
-enum SPPFNodeItem {
-    Symbol(TermKey),
+#[derive(Debug, Clone)]
+pub enum SPPFNodeItem {
+    Symbol(Term),
     LR0Item {
         expression: Rc<Vec<Term>>,
         dot: usize,
     },
-    Null,
 }
 
 impl Hash for SPPFNodeItem {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
-            Self::Symbol(term_key) => term_key.hash(state),
+            Self::Symbol(term) => term.key.hash(state),
             Self::LR0Item { expression, dot } => {
                 expression.as_ptr().hash(state);
                 dot.hash(state);
             }
-            Self::Null => (),
         }
     }
 }
@@ -42,9 +48,8 @@ impl PartialEq for SPPFNodeItem {
                     expression: expression_2,
                     dot: dot_2,
                 },
-            ) => expression_1 == expression_2 && dot_1 == dot_2,
-            (Self::Symbol(term_key_1), Self::Symbol(term_key_2)) => term_key_1 == term_key_2,
-            (Self::Null, Self::Null) => true,
+            ) => Rc::ptr_eq(expression_1, expression_2) && dot_1 == dot_2,
+            (Self::Symbol(term_1), Self::Symbol(term_2)) => term_1.key == term_2.key,
             _ => false,
         }
     }
@@ -52,36 +57,76 @@ impl PartialEq for SPPFNodeItem {
 
 impl Eq for SPPFNodeItem {}
 
-#[derive(Clone)]
-struct SPPFNodeLabel {
+impl fmt::Display for SPPFNodeItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Symbol(term) => write!(f, "{}", term),
+            Self::LR0Item { expression, dot } => {
+                let mut s = String::new();
+                for (i, term) in expression.iter().enumerate() {
+                    if i == *dot {
+                        s.push_str(". ");
+                    }
+                    s.push_str(format!("{} ", term).as_str());
+                }
+                if *dot == expression.len() {
+                    s.push('.');
+                }
+                write!(f, "{}", s.trim_end())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SPPFNodeLabel {
     item: SPPFNodeItem,
     start: usize,
     end: usize,
 }
 
 impl SPPFNodeLabel {
-    fn from_state(state: &EarleyState, end: usize) -> SPPFNodeLabel {
-        let start = state.start;
-        let item = match state.dot {
-            0 | 1 => SPPFNodeItem::Null,
-            _ => match state.at_dot() {
-                Some(_) => SPPFNodeItem::LR0Item {
-                    expression: state.expression.clone(),
-                    dot: state.dot,
-                },
-                None => SPPFNodeItem::Symbol(state.lhs),
+    pub(crate) fn symbol(term: &Term, start: usize, end: usize) -> Self {
+        Self {
+            item: SPPFNodeItem::Symbol(term.clone()),
+            start,
+            end,
+        }
+    }
+
+    pub(crate) fn prefix(expression: &Rc<Vec<Term>>, dot: usize, start: usize, end: usize) -> Self {
+        Self {
+            item: SPPFNodeItem::LR0Item {
+                expression: expression.clone(),
+                dot,
             },
-        };
-        Self { item, start, end }
+            start,
+            end,
+        }
     }
 
-    fn null() -> Self {
+    fn intermediate(state: &EarleyState, end: usize) -> Self {
         Self {
-            item: SPPFNodeItem::Null,
-            start: Default::default(),
-            end: Default::default(),
+            item: SPPFNodeItem::LR0Item {
+                expression: state.expression.clone(),
+                dot: state.dot,
+            },
+            start: state.start,
+            end,
         }
     }
+
+    pub fn item(&self) -> &SPPFNodeItem {
+        &self.item
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
 }
 
 impl Hash for SPPFNodeLabel {
@@ -100,16 +145,71 @@ impl PartialEq for SPPFNodeLabel {
 
 impl Eq for SPPFNodeLabel {}
 
-struct SPPFNode {
+impl fmt::Display for SPPFNodeLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.item, self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SPPFPackedNode {
+    pub(crate) expression: Rc<Vec<Term>>,
+    pub(crate) pivot: usize,
+    pub(crate) left: Option<SPPFNodeLabel>,
+    pub(crate) right: Option<SPPFNodeLabel>,
+}
+
+impl SPPFPackedNode {
+    pub fn expression(&self) -> &[Term] {
+        &self.expression
+    }
+
+    pub fn pivot(&self) -> usize {
+        self.pivot
+    }
+
+    pub fn left(&self) -> Option<&SPPFNodeLabel> {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> Option<&SPPFNodeLabel> {
+        self.right.as_ref()
+    }
+}
+
+impl PartialEq for SPPFPackedNode {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.expression, &other.expression)
+            && self.pivot == other.pivot
+            && self.left == other.left
+            && self.right == other.right
+    }
+}
+
+impl Eq for SPPFPackedNode {}
+
+#[derive(Debug, Clone)]
+pub struct SPPFNode {
     label: SPPFNodeLabel,
-    children: FxHashSet<SPPFNode>,
+    children: Vec<SPPFPackedNode>,
 }
 
 impl SPPFNode {
-    fn null() -> Self {
-        Self {
-            label: SPPFNodeLabel::null(),
-            children: Default::default(),
+    pub fn label(&self) -> &SPPFNodeLabel {
+        &self.label
+    }
+
+    pub fn children(&self) -> &[SPPFPackedNode] {
+        &self.children
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.children.len() > 1
+    }
+
+    fn add_child(&mut self, child: SPPFPackedNode) {
+        if !self.children.contains(&child) {
+            self.children.push(child);
         }
     }
 }
@@ -137,20 +237,492 @@ impl PartialEq for SPPFNode {
 
 impl Eq for SPPFNode {}
 
-struct SPPF {
+// the alternative of the grammar behind each alternative with atomized terminals, along with the
+// atomized position at which each of its terms starts
+#[derive(Default)]
+pub(crate) struct Origins(FxHashMap<*const Term, (Rc<Vec<Term>>, Vec<usize>)>);
+
+impl Origins {
+    pub(crate) fn insert(&mut self, atomized: &Rc<Vec<Term>>, original: &Rc<Vec<Term>>) {
+        let mut offsets = vec![0];
+        for term in original.iter() {
+            let n_atoms = match term.kind {
+                TermKind::Terminal => term.content.chars().count(),
+                TermKind::Nonterminal => 1,
+            };
+            offsets.push(offsets.last().unwrap() + n_atoms);
+        }
+        self.0
+            .insert(atomized.as_ptr(), (original.clone(), offsets));
+    }
+
+    fn get(&self, atomized: &Rc<Vec<Term>>) -> Option<&(Rc<Vec<Term>>, Vec<usize>)> {
+        self.0.get(&atomized.as_ptr())
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone)]
+pub struct SPPF {
+    root: SPPFNodeLabel,
     nodes: FxHashMap<SPPFNodeLabel, SPPFNode>,
 }
 
 impl SPPF {
-    fn insert(&mut self, label: SPPFNodeLabel) -> &mut SPPFNode {
+    pub(crate) fn from_chart(context: &ParsingContext, state: &ParsingState) -> Self {
+        SPPFBuilder::new(context, state)
+            .build()
+            .restore_terminals(&context.table.origins)
+    }
+
+    pub(crate) fn new(root: SPPFNodeLabel) -> Self {
+        let mut sppf = SPPF {
+            root: root.clone(),
+            nodes: Default::default(),
+        };
+        sppf.insert(root);
+        sppf
+    }
+
+    pub(crate) fn from_tree(tree: &ParseTree) -> Self {
+        // a single derivation found by a deterministic parser, binarised like the earley forests
+        let mut sppf = SPPF::new(SPPFNodeLabel::symbol(&tree.term, tree.start, tree.end));
+        // deep derivations are common for recursive rules, so the tree is walked without recursion
+        let mut pending = vec![tree];
+        while let Some(tree) = pending.pop() {
+            sppf.add_derivation(tree);
+            pending.extend(tree.children.iter());
+        }
+        sppf
+    }
+
+    fn add_derivation(&mut self, tree: &ParseTree) {
+        let label = SPPFNodeLabel::symbol(&tree.term, tree.start, tree.end);
+        self.insert(label.clone());
+        let Some(expression) = &tree.expression else {
+            return;
+        };
+        if tree.children.is_empty() {
+            self.add_packed(&label, expression, tree.end, None, None);
+            return;
+        }
+        let mut left = None;
+        for (i, child) in tree.children.iter().enumerate() {
+            let right = SPPFNodeLabel::symbol(&child.term, child.start, child.end);
+            let dot = i + 1;
+            let prefix = match (dot, dot == tree.children.len()) {
+                (_, true) => label.clone(),
+                (1, false) => {
+                    left = Some(right);
+                    continue;
+                }
+                (_, false) => SPPFNodeLabel::prefix(expression, dot, tree.start, child.end),
+            };
+            self.insert(prefix.clone());
+            self.add_packed(&prefix, expression, child.start, left, Some(right));
+            left = Some(prefix);
+        }
+    }
+
+    pub(crate) fn restore_terminals(&self, origins: &Origins) -> SPPF {
+        // the parsers work on single characters, the restored forest is over the alternatives of
+        // the grammar itself, with one node spanning each of its terminals
+        let mut sppf = SPPF::new(self.root.clone());
+        let mut symbols = vec![self.root.clone()];
+        let mut items = Vec::<(SPPFNodeLabel, &Term, &Rc<Vec<Term>>, usize)>::new();
+        while !symbols.is_empty() || !items.is_empty() {
+            if let Some(label) = symbols.pop() {
+                let Some(node) = self.get_node(&label) else {
+                    continue;
+                };
+                let SPPFNodeItem::Symbol(lhs) = &node.label.item else {
+                    continue;
+                };
+                let mut alternatives = Vec::<&Rc<Vec<Term>>>::new();
+                for packed in node.children.iter() {
+                    if !alternatives
+                        .iter()
+                        .any(|alternative| Rc::ptr_eq(alternative, &packed.expression))
+                    {
+                        alternatives.push(&packed.expression);
+                    }
+                }
+                for atomized in alternatives {
+                    if let Some((original, _)) = origins.get(atomized) {
+                        items.push((label.clone(), lhs, atomized, original.len()));
+                    }
+                }
+                continue;
+            }
+            let Some((label, lhs, atomized, dot)) = items.pop() else {
+                continue;
+            };
+            let Some((original, offsets)) = origins.get(atomized) else {
+                continue;
+            };
+            if dot == 0 {
+                sppf.add_packed(&label, original, label.start, None, None);
+                continue;
+            }
+            let term = &original[dot - 1];
+            let ends = match term.kind {
+                TermKind::Nonterminal => {
+                    self.atomized_pivots(lhs, atomized, offsets[dot], label.start, label.end)
+                }
+                TermKind::Terminal => {
+                    // the characters of a terminal are consecutive atoms, walked back one by one
+                    let mut pivot = label.end;
+                    for atomized_dot in (offsets[dot - 1] + 1..=offsets[dot]).rev() {
+                        let pivots =
+                            self.atomized_pivots(lhs, atomized, atomized_dot, label.start, pivot);
+                        if !pivots.iter().any(|(previous, _)| previous + 1 == pivot) {
+                            break;
+                        }
+                        pivot -= 1;
+                    }
+                    match label.end - pivot == offsets[dot] - offsets[dot - 1] {
+                        true => vec![(pivot, SPPFNodeLabel::symbol(term, pivot, label.end))],
+                        false => Vec::new(),
+                    }
+                }
+            };
+            for (pivot, right) in ends {
+                if sppf.get_node(&right).is_none() && term.is_nonterminal() {
+                    symbols.push(right.clone());
+                }
+                let left = match dot - 1 {
+                    0 => None,
+                    1 => Some(SPPFNodeLabel::symbol(&original[0], label.start, pivot)),
+                    _ => Some(SPPFNodeLabel::prefix(original, dot - 1, label.start, pivot)),
+                };
+                if let Some(left) = left.as_ref().filter(|left| sppf.get_node(left).is_none()) {
+                    match dot - 1 {
+                        1 if original[0].is_nonterminal() => symbols.push(left.clone()),
+                        1 => (),
+                        _ => items.push((left.clone(), lhs, atomized, dot - 1)),
+                    }
+                    sppf.insert(left.clone());
+                }
+                sppf.insert(right.clone());
+                sppf.add_packed(&label, original, pivot, left, Some(right));
+            }
+        }
+        sppf
+    }
+
+    fn atomized_pivots(
+        &self,
+        lhs: &Term,
+        atomized: &Rc<Vec<Term>>,
+        dot: usize,
+        start: usize,
+        end: usize,
+    ) -> Vec<(usize, SPPFNodeLabel)> {
+        // the ways the prefix of an atomized alternative up to a dot splits into the prefix
+        // before its last symbol and the node of that symbol
+        if dot == atomized.len() {
+            let label = SPPFNodeLabel::symbol(lhs, start, end);
+            return self
+                .get_node(&label)
+                .into_iter()
+                .flat_map(|node| node.children.iter())
+                .filter(|packed| Rc::ptr_eq(&packed.expression, atomized))
+                .filter_map(|packed| Some((packed.pivot, packed.right.clone()?)))
+                .collect();
+        }
+        if dot == 1 {
+            let label = SPPFNodeLabel::symbol(&atomized[0], start, end);
+            return match self.get_node(&label) {
+                Some(_) => vec![(start, label)],
+                None => Vec::new(),
+            };
+        }
+        let label = SPPFNodeLabel::prefix(atomized, dot, start, end);
+        self.get_node(&label)
+            .into_iter()
+            .flat_map(|node| node.children.iter())
+            .filter_map(|packed| Some((packed.pivot, packed.right.clone()?)))
+            .collect()
+    }
+
+    pub(crate) fn add_packed(
+        &mut self,
+        label: &SPPFNodeLabel,
+        expression: &Rc<Vec<Term>>,
+        pivot: usize,
+        left: Option<SPPFNodeLabel>,
+        right: Option<SPPFNodeLabel>,
+    ) {
+        self.insert(label.clone()).add_child(SPPFPackedNode {
+            expression: expression.clone(),
+            pivot,
+            left,
+            right,
+        });
+    }
+
+    pub(crate) fn insert(&mut self, label: SPPFNodeLabel) -> &mut SPPFNode {
         self.nodes.entry(label.clone()).or_insert(label.into())
     }
 
-    fn get_node(&self, label: &SPPFNodeLabel) -> Option<&SPPFNode> {
+    pub fn root(&self) -> &SPPFNode {
+        self.get_node(&self.root).unwrap()
+    }
+
+    pub fn get_node(&self, label: &SPPFNodeLabel) -> Option<&SPPFNode> {
         self.nodes.get(label)
     }
 
     fn get_node_mut(&mut self, label: &SPPFNodeLabel) -> Option<&mut SPPFNode> {
         self.nodes.get_mut(label)
     }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SPPFNode> {
+        self.nodes.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.nodes().any(|node| node.is_ambiguous())
+    }
+
+    pub fn tree(&self) -> Option<ParseTree> {
+        // none if every derivation in the forest passes through a cycle
+        self.trees().next()
+    }
+
+    pub fn trees(&self) -> ParseTrees<'_> {
+        ParseTrees::new(self)
+    }
+
+    pub fn count_trees(&self) -> Option<BigUint> {
+        let mut counts = FxHashMap::<&SPPFNodeLabel, BigUint>::default();
+        let mut visiting = FxHashSet::<&SPPFNodeLabel>::default();
+        let mut stack = vec![(&self.root, false)];
+        while let Some((label, expanded)) = stack.pop() {
+            let node = self.get_node(label).unwrap();
+            if expanded {
+                let count = match node.children.is_empty() {
+                    true => BigUint::from(1u32),
+                    false => node
+                        .children
+                        .iter()
+                        .map(|packed| {
+                            [&packed.left, &packed.right]
+                                .into_iter()
+                                .flatten()
+                                .map(|child| &counts[child])
+                                .product::<BigUint>()
+                        })
+                        .sum(),
+                };
+                visiting.remove(label);
+                counts.insert(label, count);
+                continue;
+            }
+            if counts.contains_key(label) {
+                continue;
+            }
+            // reaching a node again before its count is known means the forest is cyclic,
+            // in which case there are infinitely many derivations
+            if !visiting.insert(label) {
+                return None;
+            }
+            stack.push((label, true));
+            for packed in node.children.iter() {
+                for child in [&packed.left, &packed.right].into_iter().flatten() {
+                    if visiting.contains(child) {
+                        return None;
+                    }
+                    if !counts.contains_key(child) {
+                        stack.push((child, false));
+                    }
+                }
+            }
+        }
+        counts.remove(&self.root)
+    }
+}
+
+// completed items of a column, by their symbol and origin
+type Completions = NoHashMap<TermKey, NoHashMap<usize, Vec<EarleyState>>>;
+
+struct SPPFBuilder<'a> {
+    state: &'a ParsingState,
+    completed: Vec<Option<Completions>>,
+    waiting: FxHashMap<EarleyState, Vec<usize>>,
+    sppf: SPPF,
+    pending: Vec<(SPPFNodeLabel, EarleyState, usize)>,
+}
+
+impl<'a> SPPFBuilder<'a> {
+    fn new(context: &'a ParsingContext, state: &'a ParsingState) -> Self {
+        let start = context
+            .table
+            .grammar
+            .rule(context.table.grammar.start)
+            .expect("an accepted input is derived from a defined start symbol")
+            .lhs
+            .as_ref();
+        let root = SPPFNodeLabel::symbol(start, 0, context.input.len());
+        // the columns holding each item that waits for a nonterminal, so that the pivots of a
+        // completion can be found from either of its sides
+        let mut waiting = FxHashMap::<EarleyState, Vec<usize>>::default();
+        for (col, column) in state.state_table.iter().enumerate() {
+            for state in column
+                .states
+                .iter()
+                .filter(|state| state.at_dot().is_some_and(|term| term.is_nonterminal()))
+            {
+                waiting.entry(state.clone()).or_default().push(col);
+            }
+        }
+        Self {
+            state,
+            completed: state.state_table.iter().map(|_| None).collect(),
+            waiting,
+            sppf: SPPF {
+                root,
+                nodes: Default::default(),
+            },
+            pending: Vec::new(),
+        }
+    }
+
+    fn build(mut self) -> SPPF {
+        let root = self.sppf.root.clone();
+        if let SPPFNodeItem::Symbol(term) = &root.item {
+            self.symbol_node(term, root.start, root.end);
+        }
+        while let Some((label, state, end)) = self.pending.pop() {
+            self.expand(&label, &state, end);
+        }
+        self.sppf
+    }
+
+    fn index(&mut self, end: usize) {
+        let state = self.state;
+        self.completed[end].get_or_insert_with(|| {
+            let mut completions = Completions::default();
+            for completed in state.completed(end) {
+                completions
+                    .entry(completed.lhs)
+                    .or_default()
+                    .entry(completed.start)
+                    .or_default()
+                    .push(completed);
+            }
+            completions
+        });
+    }
+
+    fn origins(&self, symbol: TermKey, end: usize) -> Option<&NoHashMap<usize, Vec<EarleyState>>> {
+        self.completed[end].as_ref()?.get(&symbol)
+    }
+
+    fn symbol_node(&mut self, term: &Term, start: usize, end: usize) -> SPPFNodeLabel {
+        let label = SPPFNodeLabel::symbol(term, start, end);
+        if self.sppf.get_node(&label).is_some() {
+            return label;
+        }
+        self.sppf.insert(label.clone());
+        if term.is_nonterminal() {
+            self.index(end);
+            let completed = self
+                .origins(term.key, end)
+                .and_then(|origins| origins.get(&start))
+                .cloned()
+                .unwrap_or_default();
+            for state in completed {
+                self.pending.push((label.clone(), state, end));
+            }
+        }
+        label
+    }
+
+    fn prefix_node(&mut self, state: &EarleyState, end: usize) -> SPPFNodeLabel {
+        // binarised forest: a prefix of length one is represented by its symbol node directly
+        if state.dot == 1 {
+            return self.symbol_node(&state.expression[0], state.start, end);
+        }
+        let label = SPPFNodeLabel::intermediate(state, end);
+        if self.sppf.get_node(&label).is_none() {
+            self.sppf.insert(label.clone());
+            self.pending.push((label.clone(), state.clone(), end));
+        }
+        label
+    }
+
+    fn expand(&mut self, label: &SPPFNodeLabel, state: &EarleyState, end: usize) {
+        if state.dot == 0 {
+            self.add_packed(label, state, end, None, None);
+            return;
+        }
+        let previous = EarleyState::new(
+            state.lhs,
+            state.expression.clone(),
+            state.dot - 1,
+            state.start,
+        );
+        let symbol = &state.expression[state.dot - 1];
+        let pivots = if symbol.is_terminal() {
+            let matches = end > 0
+                && symbol
+                    .content
+                    .starts_with(self.state.state_table[end].symbol)
+                && self.state.state_table[end - 1].contains(&previous);
+            matches.then_some(end - 1).into_iter().collect()
+        } else {
+            self.index(end);
+            // candidates come from whichever side has fewer of them
+            let origins = self.origins(symbol.key, end);
+            let columns = self.waiting.get(&previous);
+            let mut pivots = match (origins, columns) {
+                (Some(origins), Some(columns)) if origins.len() <= columns.len() => origins
+                    .keys()
+                    .copied()
+                    .filter(|&pivot| self.state.state_table[pivot].contains(&previous))
+                    .collect::<Vec<usize>>(),
+                (Some(origins), Some(columns)) => columns
+                    .iter()
+                    .copied()
+                    .filter(|pivot| origins.contains_key(pivot))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            pivots.sort_unstable();
+            pivots
+        };
+        for pivot in pivots {
+            let right = self.symbol_node(symbol, pivot, end);
+            let left = (previous.dot > 0).then(|| self.prefix_node(&previous, pivot));
+            self.add_packed(label, state, pivot, left, Some(right));
+        }
+    }
+
+    fn add_packed(
+        &mut self,
+        label: &SPPFNodeLabel,
+        state: &EarleyState,
+        pivot: usize,
+        left: Option<SPPFNodeLabel>,
+        right: Option<SPPFNodeLabel>,
+    ) {
+        self.sppf
+            .get_node_mut(label)
+            .unwrap()
+            .add_child(SPPFPackedNode {
+                expression: state.expression.clone(),
+                pivot,
+                left,
+                right,
+            });
+    }
 }
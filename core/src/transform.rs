@@ -0,0 +1,426 @@
+use crate::alternatives::Alternatives;
+use crate::analysis;
+use crate::grammar::Grammar;
+use crate::rule::Rule;
+use crate::term::Term;
+use crate::types::FxHashMap;
+use crate::types::FxHashSet;
+use crate::types::NoHashMap;
+use crate::types::NoHashSet;
+use crate::types::TermKey;
+use itertools::Itertools;
+use std::rc::Rc;
+
+pub(crate) fn remove_useless_symbols(grammar: &Grammar) -> Grammar {
+    // unproductive symbols have to go first, dropping their alternatives can make further
+    // nonterminals unreachable
+    let productive = analysis::productive_nonterminals(grammar);
+    let rules = grammar
+        .rules
+        .iter()
+        .enumerate()
+        .filter(|(i, rule)| *i == 0 || productive.contains(&rule.lhs.key))
+        .map(|(_, rule)| Rule {
+            lhs: rule.lhs.clone(),
+            rhs: rule.rhs.filter(|terms| {
+                terms
+                    .iter()
+                    .all(|term| term.is_terminal() || productive.contains(&term.key))
+            }),
+        })
+        .collect::<Vec<Rule>>();
+    let productive = Grammar::from_rules(rules);
+    let reachable = analysis::reachable_nonterminals(&productive);
+    let rules = productive
+        .rules
+        .iter()
+        .filter(|rule| reachable.contains(&rule.lhs.key))
+        .cloned()
+        .collect::<Vec<Rule>>();
+    Grammar::from_rules(rules)
+}
+
+struct RuleSet {
+    order: Vec<Rc<Term>>,
+    alternatives: NoHashMap<TermKey, Vec<Vec<Term>>>,
+    names: FxHashSet<String>,
+    n_helpers: usize,
+}
+
+impl From<&Grammar> for RuleSet {
+    fn from(grammar: &Grammar) -> Self {
+        Self {
+            order: grammar.rules.iter().map(|rule| rule.lhs.clone()).collect(),
+            alternatives: grammar
+                .rules
+                .iter()
+                .map(|rule| {
+                    let alternatives = rule.rhs.alternatives.iter().map(|terms| terms.to_vec());
+                    (rule.lhs.key, alternatives.collect())
+                })
+                .collect(),
+            names: grammar
+                .rules
+                .iter()
+                .map(|rule| rule.lhs.content.clone())
+                .collect(),
+            n_helpers: 0,
+        }
+    }
+}
+
+impl RuleSet {
+    fn get(&self, key: TermKey) -> &[Vec<Term>] {
+        self.alternatives
+            .get(&key)
+            .map_or(&[], |alternatives| alternatives)
+    }
+
+    fn set(&mut self, key: TermKey, alternatives: Vec<Vec<Term>>) {
+        self.alternatives
+            .insert(key, alternatives.into_iter().unique().collect());
+    }
+
+    fn fresh(&mut self, base: &Term, kind: &str) -> Term {
+        // helper names contain a '.' like the ones generated by the readers and never collide
+        // with existing rules
+        loop {
+            self.n_helpers += 1;
+            let name = format!("{}.{}{}", base.content, kind, self.n_helpers);
+            if self.names.insert(name.clone()) {
+                return Term::nonterminal(&name);
+            }
+        }
+    }
+
+    fn add(&mut self, lhs: Term, alternatives: Vec<Vec<Term>>, first: bool) {
+        let lhs = Rc::new(lhs);
+        self.set(lhs.key, alternatives);
+        match first {
+            true => self.order.insert(0, lhs),
+            false => self.order.push(lhs),
+        }
+    }
+
+    fn build(self) -> Grammar {
+        let rules = self
+            .order
+            .iter()
+            .map(|lhs| Rule {
+                lhs: lhs.clone(),
+                rhs: Alternatives::from(self.get(lhs.key).to_vec()),
+            })
+            .collect();
+        Grammar::from_rules(rules)
+    }
+}
+
+pub(crate) fn remove_epsilon(grammar: &Grammar) -> Grammar {
+    // every alternative is expanded into all variants with and without its nullable nonterminals,
+    // only the start symbol may still derive epsilon afterwards
+    let nullable = analysis::nullable_nonterminals(grammar);
+    let mut rules = RuleSet::from(grammar);
+    for rule in grammar.rules.iter() {
+        let mut alternatives = Vec::new();
+        for terms in rule.rhs.alternatives.iter() {
+            let mut variants = vec![Vec::new()];
+            for term in terms.iter().filter(|term| !term.is_epsilon()) {
+                let omitted = match nullable.contains(&term.key) {
+                    true => variants.clone(),
+                    false => Vec::new(),
+                };
+                variants
+                    .iter_mut()
+                    .for_each(|variant| variant.push(term.clone()));
+                variants.extend(omitted);
+            }
+            alternatives.extend(variants.into_iter().filter(|variant| !variant.is_empty()));
+        }
+        rules.set(rule.lhs.key, alternatives);
+    }
+    let start = grammar.rules.first().map(|rule| rule.lhs.clone());
+    if let Some(start) = start.filter(|start| nullable.contains(&start.key)) {
+        let recursive = grammar
+            .rules
+            .iter()
+            .flat_map(|rule| rule.rhs.alternatives.iter())
+            .any(|terms| terms.iter().any(|term| term.key == start.key));
+        match recursive {
+            true => {
+                let lhs = rules.fresh(&start, "start");
+                let alternatives = vec![vec![start.as_ref().clone()], vec![Term::terminal("")]];
+                rules.add(lhs, alternatives, true);
+            }
+            false => {
+                let mut alternatives = rules.get(start.key).to_vec();
+                alternatives.push(vec![Term::terminal("")]);
+                rules.set(start.key, alternatives);
+            }
+        }
+    }
+    rules.build()
+}
+
+fn eliminate_unit_productions(rules: &mut RuleSet, scope: &NoHashSet<TermKey>) {
+    // unit productions between nonterminals in scope are replaced by the alternatives they lead
+    // to, which also breaks every cycle of unit productions among them
+    let is_unit = |terms: &Vec<Term>| {
+        terms.len() == 1 && terms[0].is_nonterminal() && scope.contains(&terms[0].key)
+    };
+    let mut replacements = Vec::new();
+    for lhs in rules.order.iter().filter(|lhs| scope.contains(&lhs.key)) {
+        let mut closure = vec![lhs.key];
+        let mut i = 0;
+        while i < closure.len() {
+            for terms in rules.get(closure[i]).iter().filter(|terms| is_unit(terms)) {
+                if !closure.contains(&terms[0].key) {
+                    closure.push(terms[0].key);
+                }
+            }
+            i += 1;
+        }
+        let alternatives = closure
+            .iter()
+            .flat_map(|&key| rules.get(key).iter())
+            .filter(|terms| !is_unit(terms))
+            .cloned()
+            .collect::<Vec<Vec<Term>>>();
+        replacements.push((lhs.key, alternatives));
+    }
+    for (key, alternatives) in replacements {
+        rules.set(key, alternatives);
+    }
+}
+
+pub(crate) fn remove_left_recursion(grammar: &Grammar) -> Grammar {
+    let components = analysis::left_recursive_components(grammar);
+    if components.is_empty() {
+        return grammar.clone();
+    }
+    // hidden left recursion through nullable prefixes only becomes direct once epsilon
+    // productions are gone, which is only necessary if a nullable symbol is involved at all
+    let nullable = analysis::nullable_nonterminals(grammar);
+    let involves_nullable = components.iter().flatten().any(|&key| {
+        nullable.contains(&key)
+            || grammar.rule(key).is_some_and(|rule| {
+                rule.rhs
+                    .alternatives
+                    .iter()
+                    .flat_map(|terms| terms.iter())
+                    .any(|term| nullable.contains(&term.key))
+            })
+    });
+    let grammar = match involves_nullable {
+        true => remove_epsilon(grammar),
+        false => grammar.clone(),
+    };
+    let mut rules = RuleSet::from(&grammar);
+    let scope = analysis::left_recursive_components(&grammar)
+        .into_iter()
+        .flatten()
+        .collect::<NoHashSet<TermKey>>();
+    eliminate_unit_productions(&mut rules, &scope);
+    let grammar = rules.build();
+    let mut rules = RuleSet::from(&grammar);
+    for component in analysis::left_recursive_components(&grammar) {
+        // the ordering of the classic algorithm is restricted to the component, left corners
+        // outside of it can never lead back into it
+        for (i, &key) in component.iter().enumerate() {
+            for &earlier in component[..i].iter() {
+                let mut alternatives = Vec::new();
+                for terms in rules.get(key).iter() {
+                    match terms.first() {
+                        Some(term) if term.key == earlier => {
+                            for prefix in rules.get(earlier).iter() {
+                                alternatives.push([prefix.as_slice(), &terms[1..]].concat());
+                            }
+                        }
+                        _ => alternatives.push(terms.clone()),
+                    }
+                }
+                rules.set(key, alternatives);
+            }
+            let (recursive, others): (Vec<_>, Vec<_>) = rules
+                .get(key)
+                .iter()
+                .cloned()
+                .partition(|terms| terms.first().is_some_and(|term| term.key == key));
+            let Some(rule) = grammar.rule(key).filter(|_| !recursive.is_empty()) else {
+                continue;
+            };
+            let lhs = rule.lhs.clone();
+            let tail = rules.fresh(&lhs, "tail");
+            let mut tails = recursive
+                .into_iter()
+                .map(|terms| [&terms[1..], std::slice::from_ref(&tail)].concat())
+                .collect::<Vec<Vec<Term>>>();
+            tails.push(vec![Term::terminal("")]);
+            let others = others
+                .into_iter()
+                .map(|terms| [terms.as_slice(), std::slice::from_ref(&tail)].concat())
+                .collect();
+            rules.set(key, others);
+            rules.add(tail, tails, false);
+        }
+    }
+    rules.build()
+}
+
+pub(crate) fn remove_unit_productions(grammar: &Grammar) -> Grammar {
+    let mut rules = RuleSet::from(grammar);
+    let scope = rules.order.iter().map(|lhs| lhs.key).collect();
+    eliminate_unit_productions(&mut rules, &scope);
+    rules.build()
+}
+
+pub(crate) fn lift_terminals(grammar: &Grammar) -> Grammar {
+    let mut rules = RuleSet::from(grammar);
+    lift(&mut rules);
+    rules.build()
+}
+
+fn lift(rules: &mut RuleSet) {
+    // terminals inside alternatives of two or more terms are replaced by a nonterminal deriving
+    // just that terminal, which is shared by every rule it occurs in
+    let mut lifted = FxHashMap::<Term, Term>::default();
+    for lhs in rules.order.clone() {
+        let mut alternatives = Vec::new();
+        for terms in rules.get(lhs.key).to_vec() {
+            if terms.len() < 2 {
+                alternatives.push(terms);
+                continue;
+            }
+            let mut alternative = Vec::with_capacity(terms.len());
+            for term in terms {
+                if !term.is_terminal() || term.is_epsilon() {
+                    alternative.push(term);
+                    continue;
+                }
+                if !lifted.contains_key(&term) {
+                    let helper = rules.fresh(&lhs, "term");
+                    rules.add(helper.clone(), vec![vec![term.clone()]], false);
+                    lifted.insert(term.clone(), helper);
+                }
+                alternative.push(lifted[&term].clone());
+            }
+            alternatives.push(alternative);
+        }
+        rules.set(lhs.key, alternatives);
+    }
+}
+
+fn binarize(rules: &mut RuleSet) {
+    // longer alternatives become chains of pairs, equal suffixes share the same helper
+    let mut suffixes = FxHashMap::<Vec<Term>, Term>::default();
+    for lhs in rules.order.clone() {
+        let alternatives = rules
+            .get(lhs.key)
+            .to_vec()
+            .into_iter()
+            .map(|terms| chain(rules, &mut suffixes, &lhs, terms))
+            .collect();
+        rules.set(lhs.key, alternatives);
+    }
+}
+
+fn chain(
+    rules: &mut RuleSet,
+    suffixes: &mut FxHashMap<Vec<Term>, Term>,
+    lhs: &Term,
+    mut terms: Vec<Term>,
+) -> Vec<Term> {
+    if terms.len() <= 2 {
+        return terms;
+    }
+    let suffix = terms.split_off(1);
+    let helper = match suffixes.get(&suffix) {
+        Some(helper) => helper.clone(),
+        None => {
+            let helper = rules.fresh(lhs, "bin");
+            suffixes.insert(suffix.clone(), helper.clone());
+            let alternative = chain(rules, suffixes, lhs, suffix);
+            rules.add(helper.clone(), vec![alternative], false);
+            helper
+        }
+    };
+    terms.push(helper);
+    terms
+}
+
+pub(crate) fn to_chomsky_normal_form(grammar: &Grammar) -> Grammar {
+    chomsky_normal_form(grammar, false)
+}
+
+pub(crate) fn chomsky_normal_form(grammar: &Grammar, keeps_unreachable: bool) -> Grammar {
+    // terminals are split into single characters, every alternative of the result is either a
+    // single character or a pair of nonterminals, only the start symbol may derive epsilon,
+    // nonterminals only reached through unit productions may be kept with the nonempty words
+    // they derive
+    let mut grammar = grammar.clone();
+    grammar.atomize_terminals();
+    let grammar = remove_epsilon(&remove_useless_symbols(&grammar));
+    let grammar = match keeps_unreachable {
+        true => remove_unit_productions(&grammar),
+        false => remove_useless_symbols(&remove_unit_productions(&grammar)),
+    };
+    let mut rules = RuleSet::from(&grammar);
+    lift(&mut rules);
+    binarize(&mut rules);
+    rules.build()
+}
+
+fn substitute(rules: &mut RuleSet, key: TermKey, replace: impl Fn(TermKey) -> bool) {
+    let mut alternatives = Vec::new();
+    for terms in rules.get(key).iter() {
+        match terms.first() {
+            Some(term) if term.is_nonterminal() && replace(term.key) => {
+                let prefixes = rules.get(term.key).iter();
+                for prefix in prefixes.filter(|prefix| !prefix.iter().all(Term::is_epsilon)) {
+                    alternatives.push([prefix.as_slice(), &terms[1..]].concat());
+                }
+            }
+            _ => alternatives.push(terms.clone()),
+        }
+    }
+    rules.set(key, alternatives);
+}
+
+pub(crate) fn to_greibach_normal_form(grammar: &Grammar) -> Grammar {
+    // the chomsky normal form guarantees nonempty alternatives without unit productions, so the
+    // tails replacing left recursion never need an epsilon alternative
+    let grammar = to_chomsky_normal_form(grammar);
+    let mut rules = RuleSet::from(&grammar);
+    let order = rules.order.clone();
+    for (i, lhs) in order.iter().enumerate() {
+        for earlier in order[..i].iter() {
+            substitute(&mut rules, lhs.key, |key| key == earlier.key);
+        }
+        let (recursive, others): (Vec<_>, Vec<_>) = rules
+            .get(lhs.key)
+            .iter()
+            .cloned()
+            .partition(|terms| terms.first().is_some_and(|term| term.key == lhs.key));
+        if recursive.is_empty() {
+            continue;
+        }
+        let tail = rules.fresh(lhs, "tail");
+        let with_tail = |terms: &[Term]| [terms, std::slice::from_ref(&tail)].concat();
+        let tails = recursive
+            .iter()
+            .flat_map(|terms| [terms[1..].to_vec(), with_tail(&terms[1..])])
+            .collect();
+        let others = others
+            .iter()
+            .flat_map(|terms| [terms.clone(), with_tail(terms)])
+            .collect();
+        rules.set(lhs.key, others);
+        rules.add(tail, tails, false);
+    }
+    // every rule now starts with a terminal or a later nonterminal and tails start with earlier
+    // rules or earlier tails, one substitution per rule in this order resolves all of them
+    let tails = rules.order[order.len()..].to_vec();
+    for lhs in order.iter().rev().chain(tails.iter()) {
+        substitute(&mut rules, lhs.key, |_| true);
+    }
+    remove_useless_symbols(&rules.build())
+}
@@ -0,0 +1,197 @@
+use crate::sppf::SPPFNode;
+use crate::sppf::SPPFNodeItem;
+use crate::sppf::SPPFNodeLabel;
+use crate::sppf::SPPFPackedNode;
+use crate::sppf::SPPF;
+use crate::term::Term;
+use crate::types::FxHashSet;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseTree {
+    pub(crate) term: Term,
+    pub(crate) expression: Option<Rc<Vec<Term>>>,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) children: Vec<ParseTree>,
+}
+
+impl ParseTree {
+    pub fn term(&self) -> &Term {
+        &self.term
+    }
+
+    pub fn expression(&self) -> Option<&[Term]> {
+        self.expression
+            .as_deref()
+            .map(|expression| expression.as_slice())
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn children(&self) -> &[ParseTree] {
+        &self.children
+    }
+}
+
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pending = vec![(self, 0)];
+        while let Some((tree, depth)) = pending.pop() {
+            writeln!(
+                f,
+                "{}{} [{}, {}]",
+                "    ".repeat(depth),
+                tree.term,
+                tree.start,
+                tree.end
+            )?;
+            pending.extend(tree.children.iter().rev().map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+}
+
+pub struct ParseTrees<'a> {
+    sppf: &'a SPPF,
+    decisions: Vec<(usize, usize)>,
+    exhausted: bool,
+}
+
+impl<'a> ParseTrees<'a> {
+    pub(crate) fn new(sppf: &'a SPPF) -> Self {
+        Self {
+            sppf,
+            decisions: Vec::new(),
+            exhausted: false,
+        }
+    }
+
+    fn advance(&mut self) {
+        // odometer over the choices made at ambiguous nodes, in the order they were encountered
+        while let Some((chosen, available)) = self.decisions.pop() {
+            if chosen + 1 < available {
+                self.decisions.push((chosen + 1, available));
+                return;
+            }
+        }
+        self.exhausted = true;
+    }
+}
+
+impl Iterator for ParseTrees<'_> {
+    type Item = ParseTree;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.exhausted {
+            let mut builder = TreeBuilder {
+                sppf: self.sppf,
+                decisions: &mut self.decisions,
+                position: 0,
+                path: Default::default(),
+            };
+            let tree = builder.build(self.sppf.root().label());
+            let position = builder.position;
+            self.decisions.truncate(position);
+            self.advance();
+            if tree.is_some() {
+                return tree;
+            }
+        }
+        None
+    }
+}
+
+enum Step<'a> {
+    Enter(&'a SPPFNodeLabel),
+    Leave(&'a SPPFNodeLabel),
+}
+
+struct TreeBuilder<'a, 'b> {
+    sppf: &'a SPPF,
+    decisions: &'b mut Vec<(usize, usize)>,
+    position: usize,
+    path: FxHashSet<&'a SPPFNodeLabel>,
+}
+
+impl<'a> TreeBuilder<'a, '_> {
+    fn choose(&mut self, node: &'a SPPFNode) -> Option<&'a SPPFPackedNode> {
+        // packed nodes leading back to a node on the current path would yield an infinite tree
+        let candidates = node
+            .children()
+            .iter()
+            .filter(|packed| {
+                [&packed.left, &packed.right]
+                    .into_iter()
+                    .flatten()
+                    .all(|child| !self.path.contains(child))
+            })
+            .collect::<Vec<&SPPFPackedNode>>();
+        match candidates.len() {
+            0 => None,
+            1 => Some(candidates[0]),
+            available => {
+                if self.position == self.decisions.len() {
+                    self.decisions.push((0, available));
+                }
+                let (chosen, _) = self.decisions[self.position];
+                self.position += 1;
+                Some(candidates[chosen])
+            }
+        }
+    }
+
+    fn build(&mut self, root: &'a SPPFNodeLabel) -> Option<ParseTree> {
+        // deep derivations are common for recursive rules, so the forest is walked without
+        // recursion, making its choices in the order a depth first walk would
+        let mut steps = vec![Step::Enter(root)];
+        let mut open = Vec::<ParseTree>::new();
+        let mut tree = None;
+        while let Some(step) = steps.pop() {
+            let label = match step {
+                Step::Enter(label) => label,
+                Step::Leave(label) => {
+                    self.path.remove(label);
+                    if let SPPFNodeItem::Symbol(_) = label.item() {
+                        let child = open.pop()?;
+                        match open.last_mut() {
+                            Some(parent) => parent.children.push(child),
+                            None => tree = Some(child),
+                        }
+                    }
+                    continue;
+                }
+            };
+            let node = self.sppf.get_node(label)?;
+            if let SPPFNodeItem::Symbol(term) = label.item() {
+                open.push(ParseTree {
+                    term: term.clone(),
+                    expression: None,
+                    start: label.start(),
+                    end: label.end(),
+                    children: Vec::new(),
+                });
+                if term.is_terminal() {
+                    steps.push(Step::Leave(node.label()));
+                    continue;
+                }
+            }
+            self.path.insert(node.label());
+            let packed = self.choose(node)?;
+            if let SPPFNodeItem::Symbol(_) = label.item() {
+                open.last_mut()?.expression = Some(packed.expression.clone());
+            }
+            steps.push(Step::Leave(node.label()));
+            steps.extend(packed.right.iter().map(Step::Enter));
+            steps.extend(packed.left.iter().map(Step::Enter));
+        }
+        tree
+    }
+}
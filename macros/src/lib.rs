@@ -1,93 +1,274 @@
 extern crate proc_macro;
-use itertools::Itertools;
-use quote::quote;
+use proc_macro::Delimiter;
+use proc_macro::Span;
+use proc_macro::TokenTree;
+use quote::quote_spanned;
+use std::collections::HashSet;
+
+type Diagnostic = (Span, String);
 
 #[proc_macro]
 pub fn grammar(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let mut grammar_builder = bnf_core::Grammar::builder();
-    let mut tokens = input.into_iter().multipeek();
-    // consume tokens, each iteration corresponds to one production rule
-    loop {
-        // if no more tokens, the previous rule was the last, therefore exit the loop
-        if tokens.peek().is_none() {
-            break;
+    let tokens = input.into_iter().collect::<Vec<TokenTree>>();
+    match build(&tokens) {
+        Ok(grammar) => bnf_core::Codify::codify(&grammar, "bnf::").parse().unwrap(),
+        Err(diagnostics) => {
+            let errors = diagnostics.into_iter().map(|(span, message)| {
+                quote_spanned! { proc_macro2::Span::from(span) => std::compile_error!(#message); }
+            });
+            quote::quote! {{ #(#errors)* bnf::Grammar::builder().build() }}.into()
         }
-        // check if the 2nd token (1st was peeked before) is the expected symbol for production rules
-        match tokens.peek() {
-            Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == '=' => (),
-            Some(tokentree) => {
-                let s = tokentree.to_string();
-                return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError(std::format!("unexpected token encountered: {}", #s).to_owned())) }.into()
-            }
-            None => return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError("expected token but got none".to_owned())) }.into(),
+    }
+}
+
+fn is_symbol(token: Option<&TokenTree>, symbol: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == symbol)
+}
+
+fn is_rule_start(tokens: &[TokenTree], i: usize) -> bool {
+    matches!(tokens.get(i), Some(TokenTree::Ident(_))) && is_symbol(tokens.get(i + 1), '=')
+}
+
+fn unexpected(token: &TokenTree) -> Diagnostic {
+    (
+        token.span(),
+        format!("unexpected token encountered: {}", token),
+    )
+}
+
+fn build(tokens: &[TokenTree]) -> Result<bnf_core::Grammar, Vec<Diagnostic>> {
+    // rules start wherever an identifier is followed by the symbol for production rules
+    let starts = (0..tokens.len())
+        .filter(|&i| is_rule_start(tokens, i))
+        .collect::<Vec<usize>>();
+    match (starts.first(), tokens.first()) {
+        (_, None) => {
+            return Err(vec![(
+                Span::call_site(),
+                "a grammar requires at least one production rule".to_owned(),
+            )])
         }
-        // consume the first token, which should correspond to the lhs of the production rule
-        let lhs = match tokens.next() {
-            Some(proc_macro::TokenTree::Ident(ident)) => std::rc::Rc::new(bnf_core::Term::nonterminal(&ident.to_string())),
-            Some(tokentree) => {
-                let s = tokentree.to_string();
-                return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError(std::format!("unexpected token encountered: {}", #s).to_owned())) }.into()
+        (Some(0), _) => (),
+        (_, Some(token)) => return Err(vec![unexpected(token)]),
+    }
+    let mut desugarer = Desugarer::default();
+    let mut rules = Vec::new();
+    let mut definitions = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(tokens.len());
+        let lhs = tokens[start].to_string();
+        desugarer.rule = lhs.clone();
+        let rhs = desugarer
+            .alternatives(&tokens[start + 2..end], tokens[start + 1].span(), true)
+            .map_err(|diagnostic| vec![diagnostic])?;
+        definitions.push((lhs.clone(), tokens[start].span()));
+        let rhs = bnf_core::Alternatives::try_from(rhs).map_err(|_| {
+            vec![(
+                tokens[start].span(),
+                format!("invalid weights in rule: {}", lhs),
+            )]
+        })?;
+        rules.push(bnf_core::Rule {
+            lhs: std::rc::Rc::new(bnf_core::Term::nonterminal(&lhs)),
+            rhs,
+        });
+    }
+    let defined = definitions
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<HashSet<&str>>();
+    let mut diagnostics = desugarer
+        .references
+        .iter()
+        .filter(|reference| !defined.contains(reference.name.as_str()))
+        .map(|reference| {
+            (
+                reference.span,
+                format!("undefined nonterminal: {}", reference.name),
+            )
+        })
+        .collect::<Vec<Diagnostic>>();
+    let reachable = reachable(&definitions[0].0, &desugarer.references);
+    diagnostics.extend(
+        definitions
+            .iter()
+            .filter(|(name, _)| !reachable.contains(name.as_str()))
+            .map(|(name, span)| (*span, format!("unreachable rule: {}", name))),
+    );
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(bnf_core::Grammar::builder()
+        .rules(&rules)
+        .helpers(&desugarer.helpers)
+        .build())
+}
+
+fn weight(tokens: &[TokenTree]) -> Result<(&[TokenTree], f64), Diagnostic> {
+    // the alternatives of a rule may end in their weight for generation, which defaults to one
+    let Some(i) = tokens.iter().position(|token| is_symbol(Some(token), '@')) else {
+        return Ok((tokens, 1.0));
+    };
+    if i == 0 {
+        return Err(unexpected(&tokens[i]));
+    }
+    match &tokens[i + 1..] {
+        [TokenTree::Literal(literal)] => match literal.to_string().parse::<f64>() {
+            Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok((&tokens[..i], weight)),
+            _ => Err((literal.span(), format!("invalid weight: {}", literal))),
+        },
+        [TokenTree::Literal(_), token, ..] | [token, ..] => Err(unexpected(token)),
+        [] => Err((
+            tokens[i].span(),
+            "expected a weight after the '@' symbol".to_owned(),
+        )),
+    }
+}
+
+fn reachable<'a>(start: &'a str, references: &'a [Reference]) -> HashSet<&'a str> {
+    // references made inside helpers are attributed to the rule the helper was generated for
+    let mut reachable = HashSet::from([start]);
+    let mut pending = vec![start];
+    while let Some(rule) = pending.pop() {
+        for reference in references.iter().filter(|reference| reference.rule == rule) {
+            if reachable.insert(&reference.name) {
+                pending.push(&reference.name);
             }
-            None => return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError("expected token but got none".to_owned())) }.into(),
-        };
-        // skip the token representing the symbol for the production rule
-        tokens.next();
-        // consume rhs of the production rule
-        let mut rhs = bnf_core::Alternatives::new();
+        }
+    }
+    reachable
+}
+
+struct Reference {
+    rule: String,
+    name: String,
+    span: Span,
+}
+
+#[derive(Default)]
+struct Desugarer {
+    rule: String,
+    helpers: Vec<bnf_core::Rule>,
+    references: Vec<Reference>,
+    n_helpers: usize,
+}
+
+impl Desugarer {
+    fn alternatives(
+        &mut self,
+        tokens: &[TokenTree],
+        span: Span,
+        is_weighted: bool,
+    ) -> Result<Vec<(Vec<bnf_core::Term>, f64)>, Diagnostic> {
+        if tokens.is_empty() {
+            return Err((
+                span,
+                "rule right-hand-side requires at least one term".to_owned(),
+            ));
+        }
+        let bars = tokens
+            .iter()
+            .filter(|token| is_symbol(Some(token), '|'))
+            .collect::<Vec<&TokenTree>>();
+        tokens
+            .split(|token| is_symbol(Some(token), '|'))
+            .enumerate()
+            .map(|(i, sequence)| match (i, sequence.is_empty()) {
+                (0, true) => Err(unexpected(bars[0])),
+                (_, true) => Err((
+                    bars[i - 1].span(),
+                    "expected a term after the '|' symbol in the right-hand-side of the rule"
+                        .to_owned(),
+                )),
+                _ => {
+                    let (sequence, weight) = match is_weighted {
+                        true => weight(sequence)?,
+                        false => (sequence, 1.0),
+                    };
+                    Ok((self.sequence(sequence)?, weight))
+                }
+            })
+            .collect()
+    }
+
+    fn sequence(&mut self, tokens: &[TokenTree]) -> Result<Vec<bnf_core::Term>, Diagnostic> {
         let mut alternative = Vec::new();
-        let mut valid_rule = false;
-        let mut expect_term = false;
-        loop {
-            // skip one token
-            tokens.peek();
-            // check if the 2nd token (1st was peeked before) is the expected symbol for production rules
-            match tokens.peek() {
-                Some(proc_macro::TokenTree::Punct(punct)) if valid_rule && punct.as_char() == '=' => {
-                    rhs.add_alternative(alternative);
-                    break;
-                },
-                Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == '=' => { return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError("rule right-hand-side requires at least one term".to_owned())) }.into() },
-                _ => ()
-            }
-            // check if we need a new alternative, or add a term to the current alternative
-            match tokens.next() {
-                Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == '|' && expect_term => { return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError("expected a term after the '|' symbol in the right-hand-side of the rule".to_owned())) }.into() },
-                Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == '|' => {
-                    rhs.add_alternative(alternative);
-                    alternative = Vec::new();
-                    expect_term = true;
-                },
-                Some(proc_macro::TokenTree::Ident(ident)) => {
-                    alternative.push(bnf_core::Term::nonterminal(&ident.to_string()));
-                    expect_term = false;
-                    valid_rule = true;
-                },
-                Some(proc_macro::TokenTree::Literal(literal)) => {
+        let mut tokens = tokens.iter().peekable();
+        while let Some(token) = tokens.next() {
+            let mut terms = match token {
+                TokenTree::Ident(ident) => {
+                    self.references.push(Reference {
+                        rule: self.rule.clone(),
+                        name: ident.to_string(),
+                        span: ident.span(),
+                    });
+                    vec![bnf_core::Term::nonterminal(&ident.to_string())]
+                }
+                TokenTree::Literal(literal) => {
                     let mut literal = literal.to_string();
                     literal.pop();
                     literal.remove(0);
-                    alternative.push(bnf_core::Term::terminal(&literal));
-                    expect_term = false;
-                    valid_rule = true;
-                },
-                Some(tokentree) => {
-                    let s = tokentree.to_string();
-                    return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError(std::format!("unexpected token encountered: {}", #s).to_owned())) }.into()
+                    vec![bnf_core::Term::terminal(&literal)]
+                }
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                    let tokens = group.stream().into_iter().collect::<Vec<_>>();
+                    let alternatives = self.alternatives(&tokens, group.span(), false)?;
+                    self.splice(alternatives.into_iter().map(|(terms, _)| terms).collect())
                 }
-                None if valid_rule && !expect_term => {
-                    rhs.add_alternative(alternative);
-                    break;
-                },
-                None => return quote! { std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Err(bnf_core::Error::InvalidGrammarSyntaxError("expected token but got none".to_owned())) }.into(),
+                tokentree => return Err(unexpected(tokentree)),
+            };
+            // postfix operators apply to the term before them and may be stacked
+            while let Some(TokenTree::Punct(punct)) = tokens.peek() {
+                terms = match punct.as_char() {
+                    '?' => {
+                        let alternatives = vec![terms, vec![bnf_core::Term::terminal("")]];
+                        vec![self.helper("opt", alternatives)]
+                    }
+                    '*' => vec![self.repetition(terms)],
+                    '+' => {
+                        let repetition = self.repetition(terms.clone());
+                        terms.push(repetition);
+                        terms
+                    }
+                    _ => break,
+                };
+                tokens.next();
             }
+            alternative.append(&mut terms);
         }
-        grammar_builder = grammar_builder.rule(bnf_core::Rule { lhs, rhs });
-        tokens.reset_peek();
+        Ok(alternative)
+    }
+
+    fn splice(&mut self, mut alternatives: Vec<Vec<bnf_core::Term>>) -> Vec<bnf_core::Term> {
+        match alternatives.len() {
+            1 => alternatives.pop().unwrap(),
+            _ => vec![self.helper("group", alternatives)],
+        }
+    }
+
+    fn repetition(&mut self, mut terms: Vec<bnf_core::Term>) -> bnf_core::Term {
+        let lhs = self.fresh("rep");
+        terms.push(lhs.clone());
+        self.add_helper(&lhs, vec![vec![bnf_core::Term::terminal("")], terms]);
+        lhs
+    }
+
+    fn helper(&mut self, kind: &str, alternatives: Vec<Vec<bnf_core::Term>>) -> bnf_core::Term {
+        let lhs = self.fresh(kind);
+        self.add_helper(&lhs, alternatives);
+        lhs
+    }
+
+    fn fresh(&mut self, kind: &str) -> bnf_core::Term {
+        // helper names contain a '.' and therefore never collide with identifiers
+        self.n_helpers += 1;
+        bnf_core::Term::nonterminal(&format!("{}.{}{}", self.rule, kind, self.n_helpers))
+    }
+
+    fn add_helper(&mut self, lhs: &bnf_core::Term, alternatives: Vec<Vec<bnf_core::Term>>) {
+        self.helpers.push(bnf_core::Rule {
+            lhs: std::rc::Rc::new(lhs.clone()),
+            rhs: bnf_core::Alternatives::from(alternatives),
+        });
     }
-    let grammar = grammar_builder.build();
-    let code = format!(
-        "std::result::Result::<bnf_core::Grammar, bnf_core::Error>::Ok({})",
-        bnf_core::Codify::codify(&grammar, "bnf::")
-    );
-    code.parse().unwrap()
 }
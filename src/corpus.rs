@@ -29,7 +29,6 @@ pub fn grammar_bnf() -> Grammar {
         rule_char       = letter | digit | "-"
         eol             = "\r\n" | "\n"
     }
-    .unwrap()
 }
 
 pub fn grammar_int() -> Grammar {
@@ -41,5 +40,12 @@ pub fn grammar_int() -> Grammar {
         nonzero_digit             = "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9"
         sign                      = "+" | "-"
     }
+}
+
+pub fn grammar_abnf_core() -> Grammar {
+    Grammar::parse_abnf(
+        "core = ALPHA / BIT / CHAR / CR / CRLF / CTL / DIGIT / DQUOTE / HEXDIG / HTAB / LF / LWSP
+              / OCTET / SP / VCHAR / WSP",
+    )
     .unwrap()
 }
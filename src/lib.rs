@@ -1,6 +1,9 @@
 pub mod corpus;
 pub use bnf_core::{
-    Alternatives, Error, ExtendedEarleyParser, GenerationStrategy, Grammar, GrammarBuilder, Rule,
-    Term,
+    Alternatives, CYKParser, CompiledGrammar, Error, ExtendedEarleyParser, GLLParser,
+    GenerationBounds, GenerationStrategy, Grammar, GrammarAnalysis, GrammarBuilder, LL1Conflict,
+    LL1ConflictKind, LRAutomaton, LRConflict, LRConflictKind, LRKind, LRParser, ParseTree,
+    ParseTrees, Parser, ParserBackend, RecoveredError, RecoveredParse, RecoveryCosts, Repair, Rule,
+    SPPFNode, SPPFNodeItem, SPPFNodeLabel, SPPFPackedNode, SyntaxError, Term, Words, SPPF,
 };
 pub use bnf_macros::*;
@@ -0,0 +1,112 @@
+use bnf::*;
+use std::collections::BTreeSet;
+
+fn set(strings: &[&str]) -> BTreeSet<String> {
+    strings.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_analysis_nullable() {
+    let grammar = corpus::grammar_bnf();
+    let analysis = grammar.analysis(1);
+    let nullable = analysis
+        .nullable()
+        .into_iter()
+        .map(|term| term.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(nullable, ["<opt_whitespace>", "<text1>", "<text2>"]);
+    assert!(analysis.is_nullable(&Term::terminal("")));
+    assert!(!analysis.is_nullable(&Term::nonterminal("rule")));
+}
+
+#[test]
+fn test_analysis_first_follow() {
+    let grammar = grammar! {
+        expr   = term expr_r
+        expr_r = "+" term expr_r | ""
+        term   = factor term_r
+        term_r = "*" factor term_r | ""
+        factor = "(" expr ")" | "id"
+    };
+    let analysis = grammar.analysis(1);
+    let nonterminal = Term::nonterminal;
+    assert_eq!(analysis.first(&nonterminal("expr")), set(&["(", "i"]));
+    assert_eq!(analysis.first(&nonterminal("expr_r")), set(&["", "+"]));
+    assert_eq!(analysis.first(&nonterminal("term_r")), set(&["", "*"]));
+    assert_eq!(analysis.follow(&nonterminal("expr")), set(&["", ")"]));
+    assert_eq!(analysis.follow(&nonterminal("expr_r")), set(&["", ")"]));
+    assert_eq!(analysis.follow(&nonterminal("term")), set(&["", ")", "+"]));
+    assert_eq!(
+        analysis.follow(&nonterminal("factor")),
+        set(&["", ")", "*", "+"])
+    );
+    let analysis = grammar.analysis(2);
+    assert_eq!(
+        analysis.first(&nonterminal("expr")),
+        set(&["((", "(i", "id"])
+    );
+    assert_eq!(
+        analysis.first(&nonterminal("term_r")),
+        set(&["", "*(", "*i"])
+    );
+    assert_eq!(
+        analysis.follow(&nonterminal("factor")),
+        set(&["", ")", "))", ")*", ")+", "*(", "*i", "+(", "+i"])
+    );
+    assert_eq!(
+        analysis.first_of(&[nonterminal("term_r"), Term::terminal(")")]),
+        set(&[")", "*(", "*i"])
+    );
+}
+
+#[test]
+fn test_analysis_ll1_conflicts() {
+    let grammar = grammar! {
+        expr   = term expr_r
+        expr_r = "+" term expr_r | ""
+        term   = factor term_r
+        term_r = "*" factor term_r | ""
+        factor = "(" expr ")" | "id"
+    };
+    assert!(grammar.ll1_conflicts().is_empty());
+    let grammar = grammar! {
+        stmt   = "if" cond | "if" cond "else" | "x" tail
+        cond   = "c" opt
+        opt    = "e" | ""
+        tail   = "" | "=" "x"
+    };
+    let conflicts = grammar.ll1_conflicts();
+    let descriptions = conflicts
+        .iter()
+        .map(|conflict| conflict.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(
+        descriptions,
+        [
+            "FIRST/FIRST conflict for <stmt> between \"if\" <cond> and \"if\" <cond> \"else\" on \"i\"",
+            "FIRST/FOLLOW conflict for <opt> between \"e\" and \"\" on \"e\"",
+        ]
+    );
+    assert_eq!(conflicts[0].kind(), LL1ConflictKind::FirstFirst);
+    assert_eq!(conflicts[0].nonterminal(), &Term::nonterminal("stmt"));
+    let [(i, _), (j, right)] = conflicts[1].alternatives();
+    assert_eq!((i, j), (0, 1));
+    assert_eq!(right, [Term::terminal("")]);
+    assert_eq!(conflicts[1].terminals(), &set(&["e"]));
+    let grammar = grammar! {
+        list = item list | ""
+        item = "a" | ""
+    };
+    let conflicts = grammar.ll1_conflicts();
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].kind(), LL1ConflictKind::FirstFollow);
+    assert_eq!(conflicts[0].terminals(), &set(&[""]));
+    assert_eq!(
+        conflicts[0].to_string(),
+        "FIRST/FOLLOW conflict for <list> between <item> <list> and \"\" on end of input"
+    );
+    assert_eq!(
+        conflicts[1].to_string(),
+        "FIRST/FOLLOW conflict for <item> between \"a\" and \"\" on \"a\""
+    );
+}
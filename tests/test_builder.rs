@@ -0,0 +1,182 @@
+use bnf::*;
+use std::rc::Rc;
+
+fn rule(lhs: &str, alternatives: Vec<Vec<Term>>) -> Rule {
+    Rule {
+        lhs: Rc::new(Term::nonterminal(lhs)),
+        rhs: Alternatives::from(alternatives),
+    }
+}
+
+#[test]
+fn test_builder_try_build() {
+    let rules = [
+        rule("a", vec![vec![Term::nonterminal("b"), Term::terminal("x")]]),
+        rule(
+            "b",
+            vec![vec![Term::terminal("y")], vec![Term::terminal("")]],
+        ),
+    ];
+    let grammar = Grammar::builder().rules(&rules).try_build().unwrap();
+    assert!(grammar.recognize("yx"));
+    let grammar = Grammar::builder()
+        .rules(&rules)
+        .start("b")
+        .try_build()
+        .unwrap();
+    assert!(grammar.recognize("y"));
+    assert!(!grammar.recognize("yx"));
+    assert_eq!(grammar, Grammar::builder().rules(&rules).start("b").build());
+}
+
+#[test]
+fn test_builder_errors() {
+    assert!(matches!(
+        Grammar::builder().try_build(),
+        Err(Error::EmptyGrammarError(_))
+    ));
+    let rules = [
+        rule(
+            "a",
+            vec![vec![Term::nonterminal("b"), Term::nonterminal("c")]],
+        ),
+        rule(
+            "b",
+            vec![vec![Term::nonterminal("c")], vec![Term::nonterminal("d")]],
+        ),
+    ];
+    match Grammar::builder().rules(&rules).try_build() {
+        Err(Error::UndefinedNonterminalError(message)) => assert_eq!(
+            message,
+            "undefined nonterminals: <c> referenced by <a>, <b>; <d> referenced by <b>"
+        ),
+        other => panic!("expected undefined nonterminals, got {:?}", other),
+    }
+    match Grammar::builder().rules(&rules).start("s").try_build() {
+        Err(Error::UndefinedStartSymbolError(message)) => {
+            assert_eq!(message, "start symbol <s> has no production rule")
+        }
+        other => panic!("expected an undefined start symbol, got {:?}", other),
+    }
+    assert!(matches!(
+        Grammar::parse_bnf("<a> ::= <b>"),
+        Err(Error::UndefinedNonterminalError(_))
+    ));
+}
+
+#[test]
+fn test_builder_unchecked() {
+    // grammars built without validation degrade gracefully instead of panicking
+    let grammar = Grammar::builder()
+        .rule(rule(
+            "a",
+            vec![vec![Term::nonterminal("b")], vec![Term::terminal("x")]],
+        ))
+        .build();
+    assert!(grammar.recognize("x"));
+    assert!(!grammar.recognize("y"));
+    assert!(matches!(
+        grammar.generate_parameterized(GenerationStrategy::UniformRHSSampling, 3),
+        Ok(_) | Err(Error::UndefinedNonterminalError(_))
+    ));
+    let grammar = Grammar::builder()
+        .rule(rule("a", vec![vec![Term::nonterminal("b")]]))
+        .build();
+    assert!(matches!(
+        grammar.generate(),
+        Err(Error::UndefinedNonterminalError(message)) if message.ends_with("<b>")
+    ));
+}
+
+#[test]
+fn test_builder_unchecked_start() {
+    // a start symbol without a rule is reported by the parsers instead of panicking
+    let grammar = Grammar::builder()
+        .rule(rule("a", vec![vec![Term::terminal("x")]]))
+        .start("missing")
+        .build();
+    assert!(!grammar.recognize("x"));
+    assert!(matches!(
+        grammar.check("x"),
+        Err(Error::UndefinedStartSymbolError(_))
+    ));
+    assert!(matches!(
+        grammar.parse("x"),
+        Err(Error::UndefinedStartSymbolError(_))
+    ));
+    assert!(matches!(
+        grammar.parse_recovering("x"),
+        Err(Error::UndefinedStartSymbolError(_))
+    ));
+    for backend in [
+        ParserBackend::Earley,
+        ParserBackend::GLL,
+        ParserBackend::CYK,
+        ParserBackend::LR(LRKind::LALR1),
+    ] {
+        if let Ok(mut parser) = grammar.parser(backend) {
+            assert!(!parser.recognize("x"));
+            assert!(matches!(
+                parser.check("x"),
+                Err(Error::UndefinedStartSymbolError(_))
+            ));
+        }
+    }
+    assert!(matches!(
+        grammar.lr_automaton(LRKind::LALR1),
+        Err(Error::UndefinedStartSymbolError(_))
+    ));
+    for strategy in [
+        GenerationStrategy::UniformRHSSampling,
+        GenerationStrategy::UniformWordSampling { length: 1 },
+        GenerationStrategy::Boltzmann { expected_size: 1 },
+    ] {
+        assert!(grammar.generate_parameterized(strategy, 3).is_err());
+    }
+    assert_eq!(grammar.count_derivations(1), Some(0u32.into()));
+    assert_eq!(grammar.enumerate().next(), None);
+}
+
+#[test]
+fn test_builder_unchecked_empty() {
+    // grammars without any rule are reported instead of panicking, even after transformations
+    for grammar in [
+        Grammar::builder().build(),
+        Grammar::builder().start("missing").build(),
+    ] {
+        for transformed in [
+            grammar.remove_useless_symbols(),
+            grammar.remove_left_recursion(),
+            grammar.remove_epsilon_rules(),
+            grammar.remove_unit_productions(),
+            grammar.lift_terminals(),
+            grammar.to_chomsky_normal_form(),
+            grammar.to_greibach_normal_form(),
+            grammar.clone(),
+        ] {
+            assert!(!transformed.recognize("x"));
+            assert!(matches!(
+                transformed.check(""),
+                Err(Error::EmptyGrammarError(_))
+            ));
+            assert!(matches!(
+                transformed.parse(""),
+                Err(Error::EmptyGrammarError(_))
+            ));
+            assert!(transformed.generate().is_err());
+        }
+        for backend in [
+            ParserBackend::Earley,
+            ParserBackend::GLL,
+            ParserBackend::CYK,
+            ParserBackend::LR(LRKind::LALR1),
+        ] {
+            if let Ok(mut parser) = grammar.parser(backend) {
+                assert!(!parser.recognize(""));
+                assert!(parser.check("").is_err());
+            }
+        }
+        assert_eq!(grammar.count_derivations(1), Some(0u32.into()));
+        assert_eq!(grammar.enumerate().next(), None);
+    }
+}
@@ -0,0 +1,317 @@
+use bnf::*;
+
+#[test]
+fn test_formats_bnf_roundtrip() {
+    for grammar in [corpus::grammar_bnf(), corpus::grammar_int()] {
+        let text = grammar.to_string();
+        assert_eq!(text.parse::<Grammar>().unwrap(), grammar);
+    }
+}
+
+#[test]
+fn test_formats_bnf_parse() {
+    let grammar = Grammar::parse_bnf(
+        "<list> ::= <item>
+                  | <item> ',' <list>
+         <item> ::= \"a\" | 'b' | \"\\u{63}\"
+        ",
+    )
+    .unwrap();
+    assert!(grammar.recognize("a,b,c"));
+    assert!(!grammar.recognize("a,,c"));
+    let grammar = Grammar::parse_bnf("<s>::=\"\"|\"(\"<s>\")\"<s>").unwrap();
+    assert!(grammar.recognize(""));
+    assert!(grammar.recognize("(()())"));
+}
+
+#[test]
+fn test_formats_bnf_errors() {
+    let message = |text: &str| match Grammar::parse_bnf(text) {
+        Err(Error::InvalidGrammarSyntaxError(message)) => message,
+        other => panic!("expected a syntax error, got {:?}", other),
+    };
+    assert_eq!(
+        message("<a> ::= \"x\"\n<b> := \"y\""),
+        "line 2, column 5: expected a term but found ':'"
+    );
+    assert_eq!(
+        message("<a> ::= \"x\" | \n"),
+        "line 2, column 1: expected a term but reached end of input"
+    );
+    assert_eq!(
+        message("<a> ::= <b> \"c"),
+        "line 1, column 13: unterminated string literal"
+    );
+    assert_eq!(
+        message("<a> = \"x\""),
+        "line 1, column 5: expected '::=' but found '='"
+    );
+    assert_eq!(
+        message("<a> ::= x"),
+        "line 1, column 9: expected a term but found 'x'"
+    );
+    assert!(matches!(
+        Grammar::parse_bnf(" \n "),
+        Err(Error::EmptyGrammarError { .. })
+    ));
+}
+
+#[test]
+fn test_formats_ebnf_roundtrip() {
+    for grammar in [corpus::grammar_bnf(), corpus::grammar_int()] {
+        let text = grammar.to_ebnf().unwrap();
+        assert_eq!(Grammar::parse_ebnf(&text).unwrap(), grammar);
+    }
+    let text = "number = [ \"-\" ], digit, { digit } ;\n\
+                digit = \"0\" | \"1\" | \"2\" | ( \"3\" | \"4\" ), [ \"'\" ] ;";
+    assert_eq!(Grammar::parse_ebnf(text).unwrap().to_ebnf().unwrap(), text);
+}
+
+#[test]
+fn test_formats_ebnf_roundtrip_names() {
+    // names which are no meta identifiers are written as special sequences
+    let grammar = Grammar::parse_bnf(
+        "<s> ::= <a-b> <a_b> <1abc> <x y>
+         <a-b> ::= \"1\"
+         <a_b> ::= \"2\"
+         <1abc> ::= \"3\"
+         <x y> ::= \"4\"",
+    )
+    .unwrap();
+    let text = grammar.to_ebnf().unwrap();
+    assert_eq!(
+        text,
+        "s = ? a-b ?, a_b, ? 1abc ?, x y ;\n\
+         ? a-b ? = \"1\" ;\n\
+         a_b = \"2\" ;\n\
+         ? 1abc ? = \"3\" ;\n\
+         x y = \"4\" ;"
+    );
+    assert_eq!(Grammar::parse_ebnf(&text).unwrap(), grammar);
+    let grammar = Grammar::parse_bnf("<s> ::= <a?>\n<a?> ::= \"x\"").unwrap();
+    assert!(matches!(
+        grammar.to_ebnf(),
+        Err(Error::UnsupportedSyntaxError(_))
+    ));
+}
+
+#[test]
+fn test_formats_ebnf_parse() {
+    let grammar = Grammar::parse_ebnf(
+        "(* a comma separated list (* with nested comments *) *)
+         list = item, { ',', item } .
+         item = letter - ( 'x' | \"y\" ) | 2 * digit | [ sign ], '(', list, ')' ;
+         letter = 'a' | 'b' | 'x' | 'y' ;
+         digit = '0' / '1' ;
+         sign = '+' ! '-' ! ;
+        ",
+    )
+    .unwrap();
+    assert!(grammar.recognize("a,b,01,(a),-(b,11)"));
+    assert!(!grammar.recognize("a,x"));
+    assert!(!grammar.recognize("0"));
+    assert!(!grammar.recognize("a,"));
+    let grammar = Grammar::parse_ebnf("digit sequence = (: '1' :), (/ '2' /) ;").unwrap();
+    assert!(grammar.recognize("1112"));
+    assert!(grammar.recognize(""));
+}
+
+#[test]
+fn test_formats_ebnf_errors() {
+    let message = |text: &str| match Grammar::parse_ebnf(text) {
+        Err(Error::InvalidGrammarSyntaxError(message)) => message,
+        other => panic!("expected a syntax error, got {:?}", other),
+    };
+    assert_eq!(
+        message("a = 'x' ;\nb := 'y' ;"),
+        "line 2, column 3: expected '=' but found ':'"
+    );
+    assert_eq!(
+        message("a = 'x', [ 'y' ;"),
+        "line 1, column 16: expected ']' but found ';'"
+    );
+    assert_eq!(
+        message("a = ? letters ;"),
+        "line 1, column 5: unterminated special sequence"
+    );
+    assert_eq!(
+        message("a = ?  ? ;"),
+        "line 1, column 5: special sequences must not be empty"
+    );
+    assert_eq!(
+        message("a = b - 'x' ;\nb = 'x', b | 'y' ;"),
+        "line 1, column 5: exceptions require both operands to derive finitely many strings"
+    );
+    assert_eq!(
+        message("a = 'x' - 'x' ;"),
+        "line 1, column 5: exception excludes every string"
+    );
+    assert!(matches!(
+        Grammar::parse_ebnf("(* nothing *)"),
+        Err(Error::EmptyGrammarError { .. })
+    ));
+}
+
+#[test]
+fn test_formats_ebnf_exception_order() {
+    // the operand of the first exception refers to the helper of a later one
+    let grammar =
+        Grammar::parse_ebnf("a = b - 'x' ;\nb = c - 'y' ;\nc = 'x' | 'y' | 'z' ;").unwrap();
+    assert!(grammar.recognize("z"));
+    assert!(!grammar.recognize("x"));
+    assert!(!grammar.recognize("y"));
+}
+
+#[test]
+fn test_formats_ebnf_exception_causes() {
+    let message = |text: &str| match Grammar::parse_ebnf(text) {
+        Err(Error::InvalidGrammarSyntaxError(message)) => message,
+        other => panic!("expected a syntax error, got {:?}", other),
+    };
+    assert_eq!(
+        message("a = b - 'x' ;\nb = 'y' - 'y' ;"),
+        "line 2, column 5: exception excludes every string"
+    );
+    assert_eq!(
+        message("a = b - 'x' ;"),
+        "line 1, column 5: exception operands use undefined nonterminal \"b\""
+    );
+    assert_eq!(
+        message("a = ( d, d, d, d, d ) - 'x' ;\nd = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' ;"),
+        "line 1, column 5: exception operands derive more than 65536 strings"
+    );
+}
+
+#[test]
+fn test_formats_ebnf_helper_names() {
+    // only the helpers a reader generated are written back as sugar
+    let grammar = Grammar::parse_bnf("<s> ::= <x.opt1>\n<x.opt1> ::= \"a\" | \"\"").unwrap();
+    assert_eq!(
+        grammar.to_ebnf().unwrap(),
+        "s = ? x.opt1 ? ;\n? x.opt1 ? = \"a\" | ;"
+    );
+}
+
+#[test]
+fn test_formats_abnf_parse() {
+    // RFC 3986, section 3.1 and 3.2.2
+    let grammar = Grammar::parse_abnf(
+        "; the authority part, without registered names\n\
+         authority   = scheme \"://\" IPv4address [ \":\" port ]\n\
+         scheme      = ALPHA *( ALPHA / DIGIT / \"+\" / \"-\" / \".\" )\n\
+         IPv4address = dec-octet \".\" dec-octet \".\" dec-octet \".\" dec-octet\n\
+         port        = *DIGIT\n\
+         dec-octet   = DIGIT                 ; 0-9\n\
+         \x20           / %x31-39 DIGIT         ; 10-99\n\
+         \x20           / \"1\" 2DIGIT            ; 100-199\n\
+         \x20           / \"2\" %x30-34 DIGIT     ; 200-249\n\
+         \x20           / \"25\" %x30-35          ; 250-255\n",
+    )
+    .unwrap();
+    assert!(grammar.recognize("http://192.168.0.255:80"));
+    assert!(grammar.recognize("svn+ssh://10.0.0.1:"));
+    assert!(!grammar.recognize("1http://10.0.0.1"));
+    assert!(!grammar.recognize("http://10.256.0.1"));
+    assert!(!grammar.recognize("http://10.01.0.1"));
+    let grammar = Grammar::parse_abnf(
+        "authority = scheme %s\"://\" IPv4address [ \":\" port ]\r\n\
+         authority =/ %d60.60 2*3( \"A\" / %b1000010 )\r\n\
+         scheme = \"HTTP\" / %i\"ftp\"\r\n\
+         IPv4address = 1*3DIGIT 3(\".\" 1*3DIGIT)\r\n\
+         port = *DIGIT\r\n",
+    )
+    .unwrap();
+    assert!(grammar.recognize("Http://127.0.0.1"));
+    assert!(grammar.recognize("fTP://10.0.0.255:8080"));
+    assert!(grammar.recognize("<<aBa"));
+    assert!(!grammar.recognize("<<ab"));
+    assert!(!grammar.recognize("http://1270.0.0.1"));
+    assert!(!grammar.recognize("http://127.0.0"));
+}
+
+#[test]
+fn test_formats_abnf_core() {
+    let grammar = corpus::grammar_abnf_core();
+    for input in ["a", "Z", "0", "F", "\r\n", "\t", "\x7f", " \r\n \t", "~"] {
+        assert!(grammar.recognize(input), "{:?}", input);
+    }
+    assert!(!grammar.recognize("ab"));
+    let grammar = Grammar::parse_abnf("hex = 1*HEXDIG\nDIGIT = \"0\"").unwrap();
+    assert!(grammar.recognize("0aF"));
+    assert!(!grammar.recognize("1"));
+}
+
+#[test]
+fn test_formats_abnf_errors() {
+    let message = |text: &str| match Grammar::parse_abnf(text) {
+        Err(Error::InvalidGrammarSyntaxError(message)) => message,
+        other => panic!("expected a syntax error, got {:?}", other),
+    };
+    assert_eq!(
+        message("a = \"x\"\nb : \"y\""),
+        "line 2, column 3: expected '=' or '=/' but found ':'"
+    );
+    assert_eq!(
+        message("a = \"x\" )"),
+        "line 1, column 9: expected end of line but found ')'"
+    );
+    assert_eq!(
+        message("a = <prose"),
+        "line 1, column 5: unterminated prose value"
+    );
+    assert_eq!(
+        message("a = 3*2\"x\""),
+        "line 1, column 5: repetition maximum is less than its minimum"
+    );
+    for text in [
+        "a = 99999999999\"x\"",
+        "a = 1*2000\"x\"",
+        "a = 99999999999999999999*\"x\"",
+    ] {
+        assert_eq!(
+            message(text),
+            "line 1, column 5: repetition count is too large"
+        );
+    }
+    assert_eq!(
+        message("a = %x5A-41"),
+        "line 1, column 12: value range is empty or too large"
+    );
+    assert!(matches!(
+        Grammar::parse_abnf("a = \"x\" <any prose>"),
+        Err(Error::UnsupportedSyntaxError(message))
+            if message == "line 1, column 9: prose value <any prose> is not supported"
+    ));
+}
+
+#[test]
+fn test_formats_weights() {
+    let bnf = Grammar::parse_bnf("<s> ::= \"a\" <s> @ 2.5 | \"b\" <s> @0 | \"\"").unwrap();
+    let ebnf = Grammar::parse_ebnf("s = \"a\", s @ 2.5 | \"b\", s @0 | ;").unwrap();
+    let abnf = Grammar::parse_abnf("s = %s\"a\" s @ 2.5 / %s\"b\" s @0 / \"\"\n").unwrap();
+    assert_eq!(bnf, ebnf);
+    assert_eq!(bnf, abnf);
+    assert_eq!(
+        bnf.to_string(),
+        "<s> ::= \"a\" <s> @ 2.5 | \"b\" <s> @ 0 | \"\""
+    );
+    assert_eq!(bnf.to_string().parse::<Grammar>().unwrap(), bnf);
+    assert_eq!(Grammar::parse_ebnf(&bnf.to_ebnf().unwrap()).unwrap(), bnf);
+    assert!(bnf.recognize("aab"));
+    let message = |result: Result<Grammar, Error>| match result {
+        Err(Error::InvalidGrammarSyntaxError(message)) => message,
+        other => panic!("expected a syntax error, got {:?}", other),
+    };
+    assert_eq!(
+        message(Grammar::parse_bnf("<s> ::= \"a\" @ | \"b\"")),
+        "line 1, column 15: expected a weight but found '|'"
+    );
+    assert_eq!(
+        message(Grammar::parse_bnf("<s> ::= \"a\" @ 1.2.3")),
+        "line 1, column 15: invalid weight"
+    );
+    assert_eq!(
+        message(Grammar::parse_ebnf("s = ( \"a\" @ 2 | \"b\" ) ;")),
+        "line 1, column 11: expected ')' but found '@'"
+    );
+}
@@ -24,16 +24,422 @@ fn test_generation_generate_parameterized() {
     }
 }
 
+#[test]
+fn test_generation_generate_parameterized_uniform_weights() {
+    // alternatives of equal weight are drawn as if the grammar had no weights at all
+    let unweighted = grammar! {
+        s = "a" s | "b" s | "c"
+    };
+    let weighted = grammar! {
+        s = "a" s @ 2 | "b" s @ 2 | "c" @ 2
+    };
+    for seed in 0..100 {
+        for strategy in [
+            GenerationStrategy::UniformRHSSampling,
+            GenerationStrategy::RecursionAvoidance,
+            GenerationStrategy::GreedyTerminals,
+        ] {
+            assert_eq!(
+                weighted.generate_parameterized(strategy, seed).unwrap(),
+                unweighted.generate_parameterized(strategy, seed).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_generation_generate_parameterized_uniform_distribution() {
+    let grammar = grammar! {
+        s = "a" | "b" | "c"
+    };
+    let mut counts = std::collections::BTreeMap::<String, usize>::new();
+    for seed in 0..900 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        *counts.entry(word).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 3);
+    assert!(counts.values().all(|&count| (250..350).contains(&count)));
+}
+
 #[test]
 #[allow(clippy::useless_vec)]
 fn test_generation_generate_parameterized_infinitely_recursive_production() {
     let grammar = grammar! {
        s = a | "a"
        a = a | a
-    }
-    .unwrap();
+    };
     assert!(matches!(
         grammar.generate_parameterized(GenerationStrategy::RecursionAvoidance, 0),
         Err(Error::InfinitelyRecursiveProductionError { .. })
     ))
 }
+
+#[test]
+fn test_generation_generate_bounded() {
+    let grammar = corpus::grammar_bnf();
+    let bounds = GenerationBounds {
+        min_length: 40,
+        max_length: 120,
+        max_steps: 2000,
+    };
+    for seed in 0..100 {
+        for strategy in &[
+            GenerationStrategy::UniformRHSSampling,
+            GenerationStrategy::GreedyTerminals,
+        ] {
+            let word = grammar
+                .generate_bounded_parameterized(*strategy, seed, bounds)
+                .unwrap();
+            assert!((40..=120).contains(&word.chars().count()), "{:?}", word);
+            assert!(grammar.recognize(word.as_str()));
+        }
+    }
+}
+
+#[test]
+fn test_generation_generate_bounded_recursive() {
+    // uniform sampling of this grammar very likely never terminates without bounds
+    let grammar = grammar! {
+        s = s s s | s | "a"
+    };
+    for seed in 0..100 {
+        let bounds = GenerationBounds {
+            max_length: 7,
+            max_steps: 50,
+            ..Default::default()
+        };
+        let word = grammar
+            .generate_bounded_parameterized(GenerationStrategy::UniformRHSSampling, seed, bounds)
+            .unwrap();
+        assert!(word.len() <= 7 && grammar.recognize(word.as_str()));
+    }
+}
+
+#[test]
+fn test_generation_generate_bounded_unsatisfiable() {
+    let grammar = grammar! {
+        s = "ab" s | "abc"
+    };
+    let unsatisfiable = [
+        GenerationBounds {
+            max_length: 2,
+            ..Default::default()
+        },
+        GenerationBounds {
+            min_length: 5,
+            max_length: 4,
+            ..Default::default()
+        },
+        GenerationBounds {
+            min_length: 4,
+            max_length: 4,
+            ..Default::default()
+        },
+        GenerationBounds {
+            min_length: 10,
+            max_steps: 3,
+            ..Default::default()
+        },
+    ];
+    for bounds in unsatisfiable {
+        assert!(matches!(
+            grammar.generate_bounded(bounds),
+            Err(Error::GenerationBoundsError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_generation_generate_bounded_single_word() {
+    // the only word of the grammar within the bounds
+    let grammar = grammar! {
+        s = "ab" s | "abc"
+    };
+    let bounds = GenerationBounds {
+        min_length: 6,
+        max_length: 8,
+        ..Default::default()
+    };
+    assert_eq!(grammar.generate_bounded(bounds).unwrap(), "abababc");
+}
+
+#[test]
+fn test_generation_enumerate() {
+    let grammar = corpus::grammar_int();
+    let mut expected = (1..1000)
+        .flat_map(|i| [i.to_string(), format!("+{}", i), format!("-{}", i)])
+        .chain(["0".to_owned()])
+        .filter(|word| word.len() <= 3)
+        .collect::<Vec<String>>();
+    expected.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    let words = grammar.enumerate().take(1000).collect::<Vec<String>>();
+    assert_eq!(words, expected[..1000]);
+}
+
+#[test]
+fn test_generation_enumerate_order() {
+    let grammar = corpus::grammar_bnf();
+    let words = grammar.enumerate().take(1000).collect::<Vec<String>>();
+    for pair in words.windows(2) {
+        assert!((pair[0].len(), &pair[0]) < (pair[1].len(), &pair[1]));
+    }
+    assert!(words.iter().all(|word| grammar.recognize(word)));
+    assert_eq!(words[0], "<A>::=\"\"\n");
+}
+
+#[test]
+fn test_generation_enumerate_finite() {
+    // ambiguous and cyclic derivations of the same words are only listed once
+    let grammar = grammar! {
+        s = s | a b | b a | ""
+        a = "x" | "" | a
+        b = "y" | "xy" | ""
+    };
+    let words = grammar.enumerate().collect::<Vec<String>>();
+    assert_eq!(words, ["", "x", "y", "xy", "yx", "xxy", "xyx"]);
+}
+
+#[test]
+fn test_generation_enumerate_empty() {
+    let grammar = grammar! {
+        s = s "a" | t
+        t = t
+    };
+    assert_eq!(grammar.enumerate().next(), None);
+}
+
+#[test]
+fn test_generation_count_derivations() {
+    let grammar = corpus::grammar_int();
+    let counts = (0..5)
+        .map(|length| grammar.count_derivations(length).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [0u32, 10, 108, 1080, 10800].map(Into::into));
+}
+
+#[test]
+fn test_generation_count_derivations_ambiguous() {
+    let grammar = grammar! {
+        e = e "+" e | "1"
+    };
+    assert_eq!(grammar.count_derivations(7), Some(5u32.into()));
+}
+
+#[test]
+fn test_generation_count_derivations_cyclic() {
+    let grammar = grammar! {
+        s = s | "a"
+    };
+    assert_eq!(grammar.count_derivations(1), None);
+    assert_eq!(grammar.count_derivations(2), Some(0u32.into()));
+}
+
+#[test]
+fn test_generation_uniform_word_sampling() {
+    // uniform sampling of alternatives would end most words early on the empty one
+    let grammar = grammar! {
+        s = "a" s | "b" s | ""
+    };
+    let mut counts = std::collections::BTreeMap::<String, usize>::new();
+    for seed in 0..800 {
+        let strategy = GenerationStrategy::UniformWordSampling { length: 3 };
+        let word = grammar.generate_parameterized(strategy, seed).unwrap();
+        *counts.entry(word).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 8);
+    assert!(counts.values().all(|&count| (60..140).contains(&count)));
+}
+
+#[test]
+fn test_generation_uniform_word_sampling_length() {
+    let grammar = corpus::grammar_bnf();
+    for seed in 0..20 {
+        let strategy = GenerationStrategy::UniformWordSampling { length: 30 };
+        let word = grammar.generate_parameterized(strategy, seed).unwrap();
+        assert_eq!(word.chars().count(), 30);
+        assert!(grammar.recognize(&word));
+    }
+}
+
+#[test]
+fn test_generation_uniform_word_sampling_no_words() {
+    let grammar = corpus::grammar_int();
+    let strategy = GenerationStrategy::UniformWordSampling { length: 0 };
+    assert!(matches!(
+        grammar.generate_parameterized(strategy, 0),
+        Err(Error::GenerationBoundsError(_))
+    ));
+}
+
+#[test]
+fn test_generation_uniform_word_sampling_cyclic() {
+    let grammar = grammar! {
+        s = s | "a"
+    };
+    let strategy = GenerationStrategy::UniformWordSampling { length: 1 };
+    assert!(matches!(
+        grammar.generate_parameterized(strategy, 0),
+        Err(Error::InfinitelyRecursiveProductionError(_))
+    ));
+}
+
+#[test]
+fn test_generation_uniform_word_sampling_max_steps() {
+    // derivations exceeding the steps of the bounds are sampled again
+    let grammar = grammar! {
+        s = x x | "ab"
+        x = "a" | "b"
+    };
+    let strategy = GenerationStrategy::UniformWordSampling { length: 2 };
+    let bounds = GenerationBounds {
+        max_steps: 1,
+        ..Default::default()
+    };
+    for seed in 0..20 {
+        let word = grammar
+            .generate_bounded_parameterized(strategy, seed, bounds)
+            .unwrap();
+        assert_eq!(word, "ab");
+    }
+}
+
+#[test]
+fn test_generation_uniform_word_sampling_max_steps_unsatisfiable() {
+    let grammar = grammar! {
+        s = x x | "ab"
+        x = "a" | "b"
+    };
+    let strategy = GenerationStrategy::UniformWordSampling { length: 2 };
+    let bounds = GenerationBounds {
+        max_steps: 0,
+        ..Default::default()
+    };
+    assert!(matches!(
+        grammar.generate_bounded_parameterized(strategy, 0, bounds),
+        Err(Error::GenerationBoundsError(_))
+    ));
+}
+
+#[test]
+fn test_generation_boltzmann() {
+    let grammar = corpus::grammar_bnf();
+    for expected_size in [50, 200] {
+        let strategy = GenerationStrategy::Boltzmann { expected_size };
+        let mut total = 0;
+        for seed in 0..20 {
+            let word = grammar.generate_parameterized(strategy, seed).unwrap();
+            let length = word.chars().count();
+            assert!((expected_size * 4 / 5..=expected_size * 6 / 5).contains(&length));
+            assert!(grammar.recognize(&word));
+            total += length;
+        }
+        assert!(total.abs_diff(20 * expected_size) <= 2 * expected_size);
+    }
+}
+
+#[test]
+fn test_generation_boltzmann_sizes() {
+    let grammar = grammar! {
+        s = "a" s | "b" s | ""
+    };
+    let strategy = GenerationStrategy::Boltzmann { expected_size: 40 };
+    for seed in 0..20 {
+        let word = grammar.generate_parameterized(strategy, seed).unwrap();
+        assert!((32..=48).contains(&word.len()));
+    }
+}
+
+#[test]
+fn test_generation_boltzmann_bounded() {
+    let grammar = grammar! {
+        s = "a" s | "b" s | ""
+    };
+    let strategy = GenerationStrategy::Boltzmann { expected_size: 40 };
+    let bounds = GenerationBounds {
+        max_length: 35,
+        ..Default::default()
+    };
+    let word = grammar
+        .generate_bounded_parameterized(strategy, 0, bounds)
+        .unwrap();
+    assert!((32..=35).contains(&word.len()));
+}
+
+#[test]
+fn test_generation_boltzmann_divergent() {
+    let grammar = grammar! {
+        s = s | "a"
+    };
+    let strategy = GenerationStrategy::Boltzmann { expected_size: 10 };
+    assert!(matches!(
+        grammar.generate_parameterized(strategy, 0),
+        Err(Error::GenerationBoundsError(_))
+    ));
+}
+
+#[test]
+fn test_generation_weighted_alternatives() {
+    let grammar = grammar! {
+        s = "a" @ 9 | "b" | "c" @ 0
+    };
+    let mut counts = std::collections::BTreeMap::<String, usize>::new();
+    for seed in 0..1000 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        *counts.entry(word).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 2);
+    assert!((850..950).contains(&counts["a"]));
+}
+
+#[test]
+fn test_generation_weighted_alternatives_invalid() {
+    let mut rhs = Alternatives::new();
+    assert!(matches!(
+        rhs.add_weighted_alternative(vec![Term::terminal("y")], -1.0),
+        Err(Error::InvalidWeightError(_))
+    ));
+    assert!(matches!(
+        Alternatives::try_from(vec![(vec![Term::terminal("y")], f64::INFINITY)]),
+        Err(Error::InvalidWeightError(_))
+    ));
+}
+
+#[test]
+fn test_generation_weighted_alternatives_zero() {
+    // the only alternative avoiding recursion is weighted zero
+    let mut rhs = Alternatives::new();
+    rhs.add_weighted_alternative(vec![Term::terminal("x"), Term::nonterminal("s")], 1.0)
+        .unwrap();
+    rhs.add_weighted_alternative(vec![Term::terminal("")], 0.0)
+        .unwrap();
+    let grammar = Grammar::builder()
+        .rule(Rule {
+            lhs: std::rc::Rc::new(Term::nonterminal("s")),
+            rhs,
+        })
+        .build();
+    assert!(matches!(
+        grammar.generate_parameterized(GenerationStrategy::RecursionAvoidance, 0),
+        Err(Error::InfinitelyRecursiveProductionError(_))
+    ));
+}
+
+#[test]
+fn test_generation_weighted_alternatives_boltzmann() {
+    // weights shift the sizes a boltzmann sampler is tuned for towards the heavier alternatives
+    let grammar = grammar! {
+        s = "a" s @ 4 | "b" s | ""
+    };
+    let strategy = GenerationStrategy::Boltzmann { expected_size: 50 };
+    let (mut a, mut b) = (0, 0);
+    for seed in 0..20 {
+        let word = grammar.generate_parameterized(strategy, seed).unwrap();
+        a += word.matches('a').count();
+        b += word.matches('b').count();
+    }
+    assert!(a > 3 * b && 5 * b > a);
+}
@@ -0,0 +1,139 @@
+use bnf::*;
+
+fn words(alphabet: &[char], max_length: usize) -> Vec<String> {
+    let mut words = vec![String::new()];
+    let mut last = vec![String::new()];
+    for _ in 0..max_length {
+        last = last
+            .iter()
+            .flat_map(|word| alphabet.iter().map(move |c| format!("{}{}", word, c)))
+            .collect();
+        words.extend(last.iter().cloned());
+    }
+    words
+}
+
+#[test]
+fn test_lr_kinds() {
+    // LR(0) needs lookaheads to decide when to stop, LALR(1) merges the states of LR(1) which
+    // tell "c" followed by "d" and by "e" apart
+    let grammar = Grammar::parse_bnf("<s> ::= 'a' <s> | 'a'").unwrap();
+    assert!(!grammar
+        .lr_automaton(LRKind::LR0)
+        .unwrap()
+        .is_deterministic());
+    assert!(grammar
+        .lr_automaton(LRKind::LALR1)
+        .unwrap()
+        .is_deterministic());
+    let grammar = Grammar::parse_bnf(
+        "<s> ::= 'a' <a> 'd' | 'b' <b> 'd' | 'a' <b> 'e' | 'b' <a> 'e'
+         <a> ::= 'c'
+         <b> ::= 'c'",
+    )
+    .unwrap();
+    let lalr = grammar.lr_automaton(LRKind::LALR1).unwrap();
+    let lr = grammar.lr_automaton(LRKind::LR1).unwrap();
+    assert!(lr.is_deterministic());
+    assert!(lr.n_states() > lalr.n_states());
+    let conflicts = lalr.conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].kind(), LRConflictKind::ReduceReduce);
+    assert_eq!(conflicts[0].lookaheads(), [Some('d'), Some('e')]);
+    assert_eq!(conflicts[0].example(), Some("ac"));
+    assert_eq!(
+        conflicts[0].to_string(),
+        "reduce/reduce conflict in state 6 on 'd', 'e' after \"a\" \"c\" (e.g. \"ac\"): \
+         reduce <b> ::= \"c\" or reduce <a> ::= \"c\""
+    );
+}
+
+#[test]
+fn test_lr_conflicts() {
+    let grammar = Grammar::parse_bnf("<e> ::= <e> '+' <e> | <e> '*' <e> | 'x'").unwrap();
+    for kind in [LRKind::LR0, LRKind::LALR1, LRKind::LR1] {
+        let automaton = grammar.lr_automaton(kind).unwrap();
+        assert_eq!(automaton.kind(), kind);
+        let conflicts = automaton.conflicts();
+        assert_eq!(conflicts.len(), 2, "{:?}", kind);
+        assert!(conflicts
+            .iter()
+            .all(|conflict| conflict.kind() == LRConflictKind::ShiftReduce));
+        let prefix = conflicts[0]
+            .prefix()
+            .iter()
+            .map(|term| term.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(prefix, ["<e>", "\"+\"", "<e>"]);
+        assert_eq!(conflicts[0].example(), Some("x+x"));
+        assert_eq!(
+            conflicts[0].reductions()[0].to_string(),
+            "<e> ::= <e> \"+\" <e>"
+        );
+    }
+    assert!(matches!(
+        grammar.lr_parser(LRKind::LALR1),
+        Err(Error::ParserConflictError(_))
+    ));
+}
+
+#[test]
+fn test_lr_parse() {
+    let grammar = Grammar::parse_bnf(
+        "<e> ::= <e> '+' <t> | <t>
+         <t> ::= <t> '*' <f> | <f>
+         <f> ::= '(' <e> ')' | 'x' | 'xy'",
+    )
+    .unwrap();
+    for kind in [LRKind::LALR1, LRKind::LR1] {
+        let mut parser = grammar.lr_parser(kind).unwrap();
+        for word in words(&['x', 'y', '+', '*', '('], 5) {
+            let word = word.replace('(', "(x)");
+            assert_eq!(
+                parser.recognize(&word),
+                grammar.recognize(&word),
+                "{}",
+                word
+            );
+            if let Ok(sppf) = parser.parse(&word) {
+                assert!(!sppf.is_ambiguous());
+                assert_eq!(
+                    sppf.tree().unwrap(),
+                    grammar.parse(&word).unwrap().tree().unwrap()
+                );
+            }
+        }
+    }
+    let mut parser = grammar.lr_parser(LRKind::LALR1).unwrap();
+    match parser.check("x+*x") {
+        Err(Error::SyntaxError(error)) => {
+            assert_eq!(error.position(), 2);
+            assert_eq!(error.expected(), ['(', 'x']);
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_lr_parse_nullable() {
+    let grammar = grammar! {
+        list = item list | ""
+        item = "a" opt
+        opt  = "b" | ""
+    };
+    let mut parser = grammar.lr_parser(LRKind::LALR1).unwrap();
+    for word in words(&['a', 'b'], 6) {
+        assert_eq!(
+            parser.recognize(&word),
+            grammar.recognize(&word),
+            "{}",
+            word
+        );
+        if let Ok(sppf) = parser.parse(&word) {
+            assert_eq!(
+                sppf.tree().unwrap(),
+                grammar.parse(&word).unwrap().tree().unwrap()
+            );
+        }
+    }
+}
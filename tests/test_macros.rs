@@ -0,0 +1,66 @@
+use bnf::*;
+
+#[test]
+fn test_macros_operators() {
+    let grammar = grammar! {
+        integer = "0" | sign? nonzero_digit digit*
+        digit   = "0" | nonzero_digit
+        nonzero_digit = ("1" | "2" | "3") | ("4" | "5" | "6" | "7" | "8" | "9")
+        sign    = "+" | "-"
+    };
+    for input in ["0", "7", "-10", "+123", "99"] {
+        assert!(grammar.recognize(input), "{}", input);
+    }
+    for input in ["", "01", "+", "-0"] {
+        assert!(!grammar.recognize(input), "{}", input);
+    }
+    let grammar = grammar! {
+        list = ("(" list? ")")+ | ("a" "b"?)*
+    };
+    for input in ["", "a", "abaa", "()", "(())()", "((a)b)"] {
+        assert_eq!(
+            grammar.recognize(input),
+            !input.contains('b') || !input.contains('('),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_macros_helpers() {
+    let grammar = grammar! {
+        digits = digit+
+        digit = "0" | "1"
+    };
+    assert_eq!(
+        grammar.to_string(),
+        "<digits> ::= <digit> <digits.rep1>\n\
+         <digits.rep1> ::= \"\" | <digit> <digits.rep1>\n\
+         <digit> ::= \"0\" | \"1\""
+    );
+    assert_eq!(
+        grammar.to_ebnf().unwrap(),
+        "digits = digit, { digit } ;\ndigit = \"0\" | \"1\" ;"
+    );
+}
+
+#[test]
+fn test_macros_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/grammar_*.rs");
+}
+
+#[test]
+fn test_macros_weights() {
+    let grammar = grammar! {
+        expr = term | expr "+" term @ 0.5
+        term = "x" @ 3 | "(" expr ")"
+    };
+    assert_eq!(
+        grammar.to_string(),
+        "<expr> ::= <term> | <expr> \"+\" <term> @ 0.5\n\
+         <term> ::= \"x\" @ 3 | \"(\" <expr> \")\""
+    );
+    assert_eq!(grammar, grammar.to_string().parse::<Grammar>().unwrap());
+}
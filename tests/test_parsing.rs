@@ -1,5 +1,28 @@
 use bnf::*;
 
+fn assert_consistent_sppf(sppf: &SPPF) {
+    for node in sppf.nodes() {
+        let label = node.label();
+        for packed in node.children() {
+            let mut end = label.end();
+            if let Some(right) = packed.right() {
+                assert!(sppf.get_node(right).is_some());
+                assert_eq!(right.start(), packed.pivot());
+                assert_eq!(right.end(), end);
+                end = right.start();
+            }
+            match packed.left() {
+                Some(left) => {
+                    assert!(sppf.get_node(left).is_some());
+                    assert_eq!(left.start(), label.start());
+                    assert_eq!(left.end(), end);
+                }
+                None => assert_eq!(label.start(), end),
+            }
+        }
+    }
+}
+
 #[test]
 fn test_parsing_recognize() {
     let grammar = corpus::grammar_bnf();
@@ -10,3 +33,397 @@ fn test_parsing_recognize() {
         assert!(grammar.recognize(word.as_str()));
     }
 }
+
+#[test]
+fn test_parsing_recognize_cyclic_chain() {
+    let grammar = grammar! {
+        s = s | "a"
+    };
+    assert!(grammar.recognize("a"));
+    assert!(!grammar.recognize("aa"));
+}
+
+#[test]
+fn test_parsing_parse() {
+    let grammar = corpus::grammar_bnf();
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let sppf = grammar.parse(word.as_str()).unwrap();
+        let root = sppf.root().label();
+        assert_eq!(root.start(), 0);
+        assert_eq!(root.end(), word.chars().count());
+        assert!(
+            matches!(root.item(), SPPFNodeItem::Symbol(term) if *term == Term::nonterminal("syntax"))
+        );
+        assert_consistent_sppf(&sppf);
+    }
+}
+
+#[test]
+fn test_parsing_parse_right_recursion() {
+    let grammar = grammar! {
+        s = "a" s | "a"
+    };
+    let sppf = grammar.parse("aaaa").unwrap();
+    assert!(!sppf.is_ambiguous());
+    assert_consistent_sppf(&sppf);
+    let mut node = sppf.root();
+    for start in 0..3 {
+        assert_eq!(node.label().start(), start);
+        assert_eq!(node.children().len(), 1);
+        node = sppf.get_node(node.children()[0].right().unwrap()).unwrap();
+    }
+    assert_eq!(node.label().start(), 3);
+    assert_eq!(node.label().end(), 4);
+}
+
+#[test]
+fn test_parsing_parse_long_right_recursion() {
+    let grammar = grammar! {
+        s = "a" s | ""
+    };
+    let sppf = grammar.parse("a".repeat(20000).as_str()).unwrap();
+    assert!(!sppf.is_ambiguous());
+    assert_eq!(sppf.count_trees(), Some(1u32.into()));
+}
+
+#[test]
+fn test_parsing_parse_ambiguous() {
+    let grammar = grammar! {
+        e = e "+" e | "1"
+    };
+    let sppf = grammar.parse("1+1+1").unwrap();
+    assert!(sppf.root().is_ambiguous());
+    assert_consistent_sppf(&sppf);
+}
+
+#[test]
+fn test_parsing_parse_nullable() {
+    let grammar = corpus::grammar_int();
+    for word in ["0", "7", "-10", "+900", "12345"] {
+        let sppf = grammar.parse(word).unwrap();
+        assert_consistent_sppf(&sppf);
+    }
+}
+
+#[test]
+fn test_parsing_parse_rejected() {
+    let grammar = corpus::grammar_int();
+    assert!(matches!(
+        grammar.parse("01"),
+        Err(Error::SyntaxError { .. })
+    ));
+}
+
+#[test]
+fn test_parsing_check() {
+    let grammar = corpus::grammar_int();
+    assert!(grammar.check("-120").is_ok());
+    let error = match grammar.check("01") {
+        Err(Error::SyntaxError(error)) => error,
+        _ => panic!("expected a syntax error"),
+    };
+    assert_eq!(error.position(), 1);
+    assert_eq!(error.found(), Some('1'));
+    assert!(error.expected().is_empty());
+    assert!(error.expected_end());
+    assert_eq!(
+        error.to_string(),
+        "unexpected '1' at line 1, column 2, expected end of input"
+    );
+    let error = match grammar.check("-") {
+        Err(Error::SyntaxError(error)) => error,
+        _ => panic!("expected a syntax error"),
+    };
+    assert_eq!(error.position(), 1);
+    assert_eq!(error.found(), None);
+    assert_eq!(
+        error.expected(),
+        &['1', '2', '3', '4', '5', '6', '7', '8', '9']
+    );
+    assert!(!error.expected_end());
+}
+
+#[test]
+fn test_parsing_check_line_column() {
+    let grammar = corpus::grammar_bnf();
+    let error = match grammar.check("<a> ::= <b>\n<c> ::= x\n") {
+        Err(Error::SyntaxError(error)) => error,
+        _ => panic!("expected a syntax error"),
+    };
+    assert_eq!(error.position(), 20);
+    assert_eq!(error.line(), 2);
+    assert_eq!(error.column(), 9);
+    assert_eq!(error.found(), Some('x'));
+    assert_eq!(error.expected(), &[' ', '"', '\'', '<']);
+}
+
+#[test]
+fn test_parsing_backends() {
+    let grammar = corpus::grammar_int();
+    let backends = [
+        ParserBackend::Earley,
+        ParserBackend::LR(LRKind::LALR1),
+        ParserBackend::LR(LRKind::LR1),
+    ];
+    let mut parsers = backends
+        .into_iter()
+        .map(|backend| grammar.parser(backend).unwrap())
+        .collect::<Vec<Box<dyn Parser>>>();
+    for word in ["0", "-10", "+900", "12345", "01", "-", ""] {
+        let results = parsers
+            .iter_mut()
+            .map(|parser| parser.parse(word).ok())
+            .collect::<Vec<Option<SPPF>>>();
+        for result in results.iter() {
+            assert_eq!(result.is_some(), grammar.recognize(word), "{}", word);
+            if let Some(sppf) = result {
+                assert_consistent_sppf(sppf);
+                assert_eq!(sppf.len(), results[0].as_ref().unwrap().len());
+                assert_eq!(
+                    sppf.tree().unwrap(),
+                    results[0].as_ref().unwrap().tree().unwrap()
+                );
+            }
+        }
+    }
+    let grammar = grammar! {
+        e = e "+" e | "1"
+    };
+    assert!(grammar.parser(ParserBackend::LR(LRKind::LALR1)).is_err());
+    assert!(grammar.recognize(&("1+".repeat(50) + "1")));
+    let word = "1+".repeat(5000) + "1";
+    let grammar = grammar! {
+        e = e "+" "1" | "1"
+    };
+    assert!(grammar.recognize(&word));
+    assert!(grammar.parse(&word).is_ok());
+    assert!(!grammar.recognize(&word[1..]));
+}
+
+#[test]
+fn test_parsing_cyk() {
+    let grammar = corpus::grammar_bnf();
+    let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+    let mut cyk = grammar.parser(ParserBackend::CYK).unwrap();
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let chars = word.chars().collect::<Vec<char>>();
+        let middle = chars.len() / 2;
+        let mutations = [
+            word.clone(),
+            chars[..middle].iter().chain(&chars[middle + 1..]).collect(),
+            chars[1..].iter().collect(),
+            chars.iter().rev().collect(),
+        ];
+        for word in mutations {
+            assert_eq!(cyk.recognize(&word), earley.recognize(&word), "{:?}", word);
+            assert_eq!(
+                format!("{:?}", cyk.check(&word)),
+                format!("{:?}", earley.check(&word)),
+                "{:?}",
+                word
+            );
+        }
+    }
+    let grammar = grammar! {
+        e = e "+" e | "1"
+    };
+    let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+    let mut cyk = grammar.parser(ParserBackend::CYK).unwrap();
+    for n in 1..6 {
+        let word = vec!["1"; n].join("+");
+        let sppf = cyk.parse(&word).unwrap();
+        assert_consistent_sppf(&sppf);
+        let count = earley.parse(&word).unwrap().count_trees();
+        assert_eq!(sppf.count_trees(), count);
+    }
+    match cyk.check("1+1++1") {
+        Err(Error::SyntaxError(error)) => {
+            assert_eq!(error.position(), 4);
+            assert_eq!(error.expected(), ['1']);
+            assert!(!error.expected_end());
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // the forests are built over the rules of the grammar, not those of its normal form
+    let grammars = [
+        grammar! {
+            e = e "+" e | e e | "1" | ""
+        },
+        grammar! {
+            s = s | "a" | s s
+        },
+        grammar! {
+            list = list item | item list | ""
+            item = "a" opt
+            opt  = "b" | ""
+        },
+        grammar! {
+            s = a "b" c
+            a = "a" | b
+            b = "a" "a"
+            c = "" | "c" c
+        },
+    ];
+    for grammar in grammars {
+        let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+        let mut cyk = grammar.parser(ParserBackend::CYK).unwrap();
+        for word in [
+            "", "1", "a", "1+1", "ab", "aa", "1+11", "aba", "1+1+1", "abab", "aabcc",
+        ] {
+            match earley.parse(word) {
+                Ok(expected) => {
+                    let sppf = cyk.parse(word).unwrap();
+                    assert_consistent_sppf(&sppf);
+                    assert_eq!(sppf.count_trees(), expected.count_trees(), "{:?}", word);
+                    assert_eq!(sppf.len(), expected.len(), "{:?}", word);
+                }
+                Err(error) => assert_eq!(
+                    format!("{:?}", cyk.check(word)),
+                    format!("{:?}", Err::<(), _>(error)),
+                    "{:?}",
+                    word
+                ),
+            }
+        }
+    }
+    let grammar = corpus::grammar_int();
+    let mut cyk = grammar.parser(ParserBackend::CYK).unwrap();
+    for word in ["0", "-10", "+900", "", "01", "1-"] {
+        assert_eq!(cyk.recognize(word), grammar.recognize(word), "{}", word);
+    }
+}
+
+#[test]
+fn test_parsing_gll() {
+    let grammar = corpus::grammar_bnf();
+    let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+    let mut gll = grammar.parser(ParserBackend::GLL).unwrap();
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let chars = word.chars().collect::<Vec<char>>();
+        let middle = chars.len() / 2;
+        let mutations = [
+            word.clone(),
+            chars[..middle].iter().chain(&chars[middle + 1..]).collect(),
+            chars[1..].iter().collect(),
+        ];
+        for word in mutations {
+            match earley.parse(&word) {
+                Ok(expected) => {
+                    let sppf = gll.parse(&word).unwrap();
+                    assert_consistent_sppf(&sppf);
+                    assert_eq!(sppf.count_trees(), expected.count_trees(), "{:?}", word);
+                }
+                Err(_) => assert!(!gll.recognize(&word), "{:?}", word),
+            }
+        }
+    }
+    let grammars = [
+        grammar! {
+            e = e "+" e | e e | "1" | ""
+        },
+        grammar! {
+            s = s | "a" | s s
+        },
+        grammar! {
+            list = list item | item list | ""
+            item = "a" opt
+            opt  = "b" | ""
+        },
+    ];
+    for grammar in grammars {
+        let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+        let mut gll = grammar.parser(ParserBackend::GLL).unwrap();
+        for word in [
+            "", "1", "a", "1+1", "ab", "aa", "1+11", "aba", "1+1+1", "abab",
+        ] {
+            match earley.parse(word) {
+                Ok(expected) => {
+                    let sppf = gll.parse(word).unwrap();
+                    assert_consistent_sppf(&sppf);
+                    assert_eq!(sppf.count_trees(), expected.count_trees(), "{:?}", word);
+                    assert_eq!(sppf.len(), expected.len(), "{:?}", word);
+                }
+                Err(_) => assert!(!gll.recognize(word), "{:?}", word),
+            }
+        }
+    }
+    let grammar = grammar! {
+        e = e "+" t | t
+        t = "1" | "(" e ")"
+    };
+    let mut gll = grammar.parser(ParserBackend::GLL).unwrap();
+    match gll.check("1+(1+)") {
+        Err(Error::SyntaxError(error)) => {
+            assert_eq!(error.position(), 5);
+            assert_eq!(error.expected(), ['(', '1']);
+            assert!(!error.expected_end());
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_parsing_compiled_grammar() {
+    let grammar = corpus::grammar_bnf();
+    let mut compiled = grammar.compile();
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let invalid = format!("{}<", word);
+        assert!(compiled.recognize(&word));
+        assert!(!compiled.recognize(&invalid));
+        assert_eq!(
+            compiled.parse(&word).unwrap().count_trees(),
+            grammar.parse(&word).unwrap().count_trees()
+        );
+        assert_eq!(
+            format!("{:?}", compiled.check(&invalid)),
+            format!("{:?}", grammar.check(&invalid))
+        );
+    }
+    let grammar = grammar! {
+        e = e "+" "1" | "1"
+    };
+    let mut compiled = grammar.compile();
+    let long = vec!["1"; 5001].join("+");
+    assert!(compiled.recognize(&long));
+    assert!(!compiled.recognize(&long[1..]));
+    assert!(compiled.recognize("1+1"));
+    assert_eq!(
+        compiled.parse(&long).unwrap().count_trees(),
+        Some(1u8.into())
+    );
+    let recovered = compiled.parse_recovering("1+1++1").unwrap();
+    assert_eq!(recovered.errors().len(), 1);
+    assert!(compiled.recognize("1"));
+    // the grammar compiles itself once for parsing, which leaves it equal to its copies
+    let copy = grammar.clone();
+    assert!(grammar.recognize(&long));
+    assert!(grammar.recognize("1+1"));
+    assert_eq!(grammar, copy);
+}
+
+#[test]
+fn test_parsing_compiled_grammar_trees() {
+    // long inputs switch to the lalr automaton, whose forests match those of the earley parser
+    let grammar = grammar! {
+        s = "ab" s | ""
+    };
+    let long = "ab".repeat(100);
+    let mut compiled = grammar.compile();
+    let mut earley = grammar.parser(ParserBackend::Earley).unwrap();
+    assert_eq!(
+        compiled.parse(&long).unwrap().tree(),
+        earley.parse(&long).unwrap().tree()
+    );
+}
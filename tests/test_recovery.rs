@@ -0,0 +1,99 @@
+use bnf::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+#[test]
+fn test_recovery_clean() {
+    let grammar = corpus::grammar_int();
+    let recovered = grammar.parse_recovering("-120").unwrap();
+    assert!(recovered.is_clean());
+    assert_eq!(recovered.input(), "-120");
+    assert_eq!(recovered.forest().count_trees(), Some(1u32.into()));
+}
+
+#[test]
+fn test_recovery_deletion() {
+    let grammar = corpus::grammar_int();
+    let recovered = grammar.parse_recovering("1x2").unwrap();
+    assert_eq!(recovered.input(), "12");
+    assert_eq!(recovered.errors().len(), 1);
+    let error = &recovered.errors()[0];
+    assert_eq!(error.repair(), &Repair::Deletion("x".to_owned()));
+    assert_eq!(error.error().position(), 1);
+    assert_eq!(error.error().found(), Some('x'));
+}
+
+#[test]
+fn test_recovery_insertion() {
+    let grammar = corpus::grammar_bnf();
+    let recovered = grammar
+        .parse_recovering("<a> ::= <b\n<c> ::= \"d\"\n")
+        .unwrap();
+    assert_eq!(recovered.input(), "<a> ::= <b>\n<c> ::= \"d\"\n");
+    assert_eq!(recovered.errors().len(), 1);
+    let error = &recovered.errors()[0];
+    assert_eq!(error.repair(), &Repair::Insertion('>'));
+    assert_eq!(error.error().position(), 10);
+    assert_eq!(error.error().line(), 1);
+    assert_eq!(error.error().column(), 11);
+}
+
+#[test]
+fn test_recovery_completion() {
+    let grammar = corpus::grammar_bnf();
+    let recovered = grammar.parse_recovering("<a> ::= <b").unwrap();
+    assert_eq!(recovered.input(), "<a> ::= <b>\n");
+    assert_eq!(
+        recovered
+            .errors()
+            .iter()
+            .map(|error| error.repair().clone())
+            .collect::<Vec<Repair>>(),
+        vec![Repair::Insertion('>'), Repair::Insertion('\n')]
+    );
+    assert!(recovered
+        .errors()
+        .iter()
+        .all(|error| error.error().found().is_none()));
+}
+
+#[test]
+fn test_recovery_costs() {
+    let grammar = corpus::grammar_int();
+    let costs = RecoveryCosts {
+        insertion: 1,
+        deletion: 1,
+        max_cost: 1,
+    };
+    assert!(grammar.parse_recovering_parameterized("1x2", costs).is_ok());
+    assert!(matches!(
+        grammar.parse_recovering_parameterized("1xy2", costs),
+        Err(Error::SyntaxError { .. })
+    ));
+}
+
+#[test]
+fn test_recovery_corrupted() {
+    let grammar = corpus::grammar_bnf();
+    let mut rng = StdRng::seed_from_u64(0);
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let mut corrupted = word.chars().collect::<Vec<char>>();
+        for _ in 0..3 {
+            let position = rng.gen_range(0..=corrupted.len());
+            match rng.gen_bool(0.5) {
+                true if position < corrupted.len() => {
+                    corrupted.remove(position);
+                }
+                _ => corrupted.insert(position, rng.gen_range('!'..='~')),
+            }
+        }
+        let corrupted = corrupted.into_iter().collect::<String>();
+        let recovered = grammar.parse_recovering(&corrupted).unwrap();
+        assert!(grammar.recognize(recovered.input()));
+        assert_eq!(recovered.is_clean(), grammar.recognize(&corrupted));
+    }
+}
@@ -0,0 +1,258 @@
+use bnf::*;
+
+fn names(terms: Vec<&Term>) -> Vec<String> {
+    terms.into_iter().map(|term| term.to_string()).collect()
+}
+
+#[test]
+fn test_transforms_useless_symbols() {
+    let grammar = corpus::grammar_bnf();
+    assert!(grammar.unproductive_nonterminals().is_empty());
+    assert!(grammar.unreachable_nonterminals().is_empty());
+    assert_eq!(grammar.remove_useless_symbols(), grammar);
+    let grammar = Grammar::parse_bnf(
+        "<s> ::= <a> | <b> 'x' | 'y'
+         <a> ::= <a> | <a>
+         <b> ::= <b> <c> | <c>
+         <c> ::= 'z' <a> | 'z'
+         <d> ::= 'w'
+         <e> ::= <a> <d>",
+    )
+    .unwrap();
+    assert_eq!(names(grammar.unproductive_nonterminals()), ["<e>", "<a>"]);
+    assert_eq!(names(grammar.unreachable_nonterminals()), ["<d>", "<e>"]);
+    let cleaned = grammar.remove_useless_symbols();
+    assert_eq!(
+        cleaned.to_string(),
+        "<s> ::= <b> \"x\" | \"y\"\n\
+         <b> ::= <b> <c> | <c>\n\
+         <c> ::= \"z\""
+    );
+    for input in ["y", "zx", "zzzx", "w", "zzx"] {
+        assert_eq!(
+            cleaned.recognize(input),
+            grammar.recognize(input),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_transforms_useless_start() {
+    let grammar = Grammar::parse_bnf("<s> ::= <s> 'a'\n<t> ::= 'b'").unwrap();
+    assert_eq!(names(grammar.unproductive_nonterminals()), ["<s>"]);
+    let cleaned = grammar.remove_useless_symbols();
+    assert_eq!(cleaned.to_string(), "<s> ::= ");
+    assert!(!cleaned.recognize(""));
+    assert!(!cleaned.recognize("b"));
+}
+
+fn words(alphabet: &[char], max_length: usize) -> Vec<String> {
+    let mut words = vec![String::new()];
+    let mut last = vec![String::new()];
+    for _ in 0..max_length {
+        last = last
+            .iter()
+            .flat_map(|word| alphabet.iter().map(move |c| format!("{}{}", word, c)))
+            .collect();
+        words.extend(last.iter().cloned());
+    }
+    words
+}
+
+fn assert_equivalent(left: &Grammar, right: &Grammar, alphabet: &[char], max_length: usize) {
+    for word in words(alphabet, max_length) {
+        assert_eq!(left.recognize(&word), right.recognize(&word), "{:?}", word);
+    }
+}
+
+#[test]
+fn test_transforms_left_recursion_cycles() {
+    let cycles = |grammar: &Grammar| {
+        grammar
+            .left_recursion()
+            .into_iter()
+            .map(names)
+            .collect::<Vec<Vec<String>>>()
+    };
+    assert_eq!(cycles(&corpus::grammar_bnf()), [["<rule_name>"]]);
+    assert_eq!(cycles(&corpus::grammar_int()), [["<digits>"]]);
+    let grammar = Grammar::parse_bnf(
+        "<s> ::= <a> 'a' | 'b'
+         <a> ::= <s> 'c' | <o> <a> 'd' | 'e'
+         <o> ::= '' | 'o'",
+    )
+    .unwrap();
+    assert_eq!(cycles(&grammar), [vec!["<s>", "<a>"], vec!["<a>"]]);
+    assert!(cycles(&Grammar::parse_bnf("<s> ::= 'a' <s> | 'b'").unwrap()).is_empty());
+}
+
+#[test]
+fn test_transforms_remove_left_recursion() {
+    let grammars = [
+        ("<s> ::= <s> 'a' | 'b'", vec!['a', 'b']),
+        (
+            "<s> ::= <a> 'a' | 'b'\n<a> ::= <s> 'c' | 'd'",
+            vec!['a', 'b', 'c', 'd'],
+        ),
+        (
+            "<s> ::= <b> <s> 'x' | 'y'\n<b> ::= '' | 'b'",
+            vec!['b', 'x', 'y'],
+        ),
+        ("<s> ::= <s> <s> | '(' <s> ')' | ''", vec!['(', ')']),
+        ("<s> ::= <a> | 'a'\n<a> ::= <s> | <s> 'b'", vec!['a', 'b']),
+        (
+            "<e> ::= <e> '+' <t> | <t>\n<t> ::= <t> '*' <f> | <f>\n<f> ::= '(' <e> ')' | 'x'",
+            vec!['x', '+', '*', '('],
+        ),
+    ];
+    for (text, alphabet) in grammars {
+        let grammar = Grammar::parse_bnf(text).unwrap();
+        let transformed = grammar.remove_left_recursion();
+        assert!(transformed.left_recursion().is_empty(), "{}", transformed);
+        assert_equivalent(&grammar, &transformed, &alphabet, 6);
+    }
+    let grammar = corpus::grammar_int();
+    let transformed = grammar.remove_left_recursion();
+    assert!(transformed.left_recursion().is_empty());
+    assert_equivalent(&grammar, &transformed, &['0', '1', '-'], 4);
+    let grammar = Grammar::parse_bnf("<s> ::= <s> 'a' | 'b'").unwrap();
+    assert_eq!(
+        grammar.remove_left_recursion().to_string(),
+        "<s> ::= \"b\" <s.tail1>\n<s.tail1> ::= \"a\" <s.tail1> | \"\""
+    );
+}
+
+fn normal_form_grammars() -> Vec<(Grammar, Vec<char>)> {
+    let grammars = [
+        ("<s> ::= <s> 'a' | 'bc'", vec!['a', 'b', 'c']),
+        ("<s> ::= <s> <s> | '(' <s> ')' | ''", vec!['(', ')']),
+        ("<s> ::= <a> | 'a'\n<a> ::= <s> | <s> 'b'", vec!['a', 'b']),
+        (
+            "<s> ::= <b> <s> 'x' | 'y' <b> <b> <b>\n<b> ::= '' | 'b'",
+            vec!['b', 'x', 'y'],
+        ),
+        (
+            "<e> ::= <e> '+' <t> | <t>\n<t> ::= <t> '*' <f> | <f>\n<f> ::= '(' <e> ')' | 'x'",
+            vec!['x', '+', '*', '('],
+        ),
+    ];
+    let mut grammars = grammars
+        .into_iter()
+        .map(|(text, alphabet)| (Grammar::parse_bnf(text).unwrap(), alphabet))
+        .collect::<Vec<(Grammar, Vec<char>)>>();
+    grammars.push((corpus::grammar_int(), vec!['0', '1', '-']));
+    grammars
+}
+
+fn productions(grammar: &Grammar) -> Vec<(String, Vec<Vec<String>>)> {
+    grammar
+        .to_string()
+        .lines()
+        .map(|line| {
+            let (lhs, rhs) = line.split_once(" ::= ").unwrap();
+            let alternatives = rhs.split(" | ").map(|terms| {
+                let terms = terms.split(' ').map(|term| term.to_owned());
+                terms.collect::<Vec<String>>()
+            });
+            (lhs.to_owned(), alternatives.collect())
+        })
+        .collect()
+}
+
+fn assert_epsilon_only_at_start(productions: &[(String, Vec<Vec<String>>)]) {
+    let start = &productions[0].0;
+    for (i, (lhs, alternatives)) in productions.iter().enumerate() {
+        for terms in alternatives.iter() {
+            assert!(terms != &["\"\""] || i == 0, "{} derives epsilon", lhs);
+        }
+    }
+    if productions[0].1.iter().any(|terms| terms == &["\"\""]) {
+        let mut referenced = productions
+            .iter()
+            .flat_map(|(_, alternatives)| alternatives.iter().flatten());
+        assert!(referenced.all(|term| term != start));
+    }
+}
+
+#[test]
+fn test_transforms_chomsky_normal_form() {
+    for (grammar, alphabet) in normal_form_grammars() {
+        let transformed = grammar.to_chomsky_normal_form();
+        let productions = productions(&transformed);
+        assert_epsilon_only_at_start(&productions);
+        for terms in productions
+            .iter()
+            .flat_map(|(_, alternatives)| alternatives)
+        {
+            let is_pair = terms.len() == 2 && terms.iter().all(|term| term.starts_with('<'));
+            let is_char = terms.len() == 1 && terms[0].starts_with('"') && terms[0].len() == 3;
+            assert!(is_pair || is_char || terms == &["\"\""], "{}", transformed);
+        }
+        assert_equivalent(&grammar, &transformed, &alphabet, 6);
+    }
+    let grammar = Grammar::parse_bnf("<s> ::= <s> 'a' | 'bc'").unwrap();
+    assert_eq!(
+        grammar.to_chomsky_normal_form().to_string(),
+        "<s> ::= <s> <s.term1> | <s.term2> <s.term3>\n\
+         <s.term1> ::= \"a\"\n\
+         <s.term2> ::= \"b\"\n\
+         <s.term3> ::= \"c\""
+    );
+}
+
+#[test]
+fn test_transforms_greibach_normal_form() {
+    for (grammar, alphabet) in normal_form_grammars() {
+        let transformed = grammar.to_greibach_normal_form();
+        let productions = productions(&transformed);
+        assert_epsilon_only_at_start(&productions);
+        for terms in productions
+            .iter()
+            .flat_map(|(_, alternatives)| alternatives)
+        {
+            let is_prefixed =
+                terms[0].starts_with('"') && terms[1..].iter().all(|term| term.starts_with('<'));
+            assert!(is_prefixed, "{}", transformed);
+        }
+        assert!(transformed.left_recursion().is_empty());
+        assert_equivalent(&grammar, &transformed, &alphabet, 6);
+    }
+    let grammar = Grammar::parse_bnf("<s> ::= <s> 'a' | 'bc'").unwrap();
+    assert_eq!(
+        grammar.to_greibach_normal_form().to_string(),
+        "<s> ::= \"b\" <s.term3> | \"b\" <s.term3> <s.tail1>\n\
+         <s.term3> ::= \"c\"\n\
+         <s.tail1> ::= \"a\" | \"a\" <s.tail1>"
+    );
+}
+
+#[test]
+fn test_transforms_normal_form_steps() {
+    let grammar = Grammar::parse_bnf("<s> ::= <a> 'b' <a>\n<a> ::= <s> | 'a' | ''").unwrap();
+    let without_epsilon = grammar.remove_epsilon_rules();
+    assert_eq!(
+        without_epsilon.to_string(),
+        "<s> ::= <a> \"b\" <a> | \"b\" <a> | <a> \"b\" | \"b\"\n\
+         <a> ::= <s> | \"a\""
+    );
+    let without_units = without_epsilon.remove_unit_productions();
+    assert_eq!(
+        without_units.to_string(),
+        "<s> ::= <a> \"b\" <a> | \"b\" <a> | <a> \"b\" | \"b\"\n\
+         <a> ::= \"a\" | <a> \"b\" <a> | \"b\" <a> | <a> \"b\" | \"b\""
+    );
+    assert_eq!(
+        Grammar::parse_bnf("<s> ::= 'a' <s> 'b' | 'ab'")
+            .unwrap()
+            .lift_terminals()
+            .to_string(),
+        "<s> ::= <s.term1> <s> <s.term2> | \"ab\"\n\
+         <s.term1> ::= \"a\"\n\
+         <s.term2> ::= \"b\""
+    );
+    for transformed in [without_epsilon, without_units] {
+        assert_equivalent(&grammar, &transformed, &['a', 'b'], 6);
+    }
+}
@@ -0,0 +1,159 @@
+use bnf::*;
+use std::collections::HashSet;
+
+fn leaves(tree: &ParseTree) -> String {
+    match tree.children().is_empty() {
+        true if tree.term().is_terminal() => tree.term().terminal_content().unwrap().to_owned(),
+        true => String::new(),
+        false => tree.children().iter().map(leaves).collect(),
+    }
+}
+
+#[test]
+fn test_trees_tree() {
+    let grammar = corpus::grammar_bnf();
+    for seed in 0..100 {
+        let word = grammar
+            .generate_parameterized(GenerationStrategy::UniformRHSSampling, seed)
+            .unwrap();
+        let tree = grammar.parse(word.as_str()).unwrap().tree().unwrap();
+        assert_eq!(tree.term(), &Term::nonterminal("syntax"));
+        assert_eq!(leaves(&tree), word);
+    }
+}
+
+#[test]
+fn test_trees_tree_spans() {
+    let grammar = corpus::grammar_int();
+    let tree = grammar.parse("-120").unwrap().tree().unwrap();
+    assert_eq!(tree.term(), &Term::nonterminal("integer"));
+    assert_eq!(
+        tree.expression().unwrap(),
+        &[
+            Term::nonterminal("sign"),
+            Term::nonterminal("unsigned_nonzero_integer")
+        ]
+    );
+    let spans = tree
+        .children()
+        .iter()
+        .map(|child| (child.term().clone(), child.start(), child.end()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            (Term::nonterminal("sign"), 0, 1),
+            (Term::nonterminal("unsigned_nonzero_integer"), 1, 4),
+        ]
+    );
+}
+
+#[test]
+fn test_trees_terminals() {
+    let grammar = grammar! {
+        s = "ab" t
+        t = "cd" | ""
+    };
+    let tree = grammar.parse("abcd").unwrap().tree().unwrap();
+    assert_eq!(
+        tree.expression().unwrap(),
+        &[Term::terminal("ab"), Term::nonterminal("t")]
+    );
+    let spans = tree
+        .children()
+        .iter()
+        .map(|child| (child.term().clone(), child.start(), child.end()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![(Term::terminal("ab"), 0, 2), (Term::nonterminal("t"), 2, 4)]
+    );
+    assert_eq!(
+        tree.children()[1].children()[0].term(),
+        &Term::terminal("cd")
+    );
+    let tree = grammar.parse("ab").unwrap().tree().unwrap();
+    let epsilon = &tree.children()[1].children()[0];
+    assert_eq!(epsilon.term(), &Term::terminal(""));
+    assert_eq!((epsilon.start(), epsilon.end()), (2, 2));
+}
+
+#[test]
+fn test_trees_terminals_backends() {
+    let grammar = grammar! {
+        s = "ab" t
+        t = "cd" | ""
+    };
+    for word in ["abcd", "ab"] {
+        let tree = grammar.parse(word).unwrap().tree().unwrap();
+        for backend in [
+            ParserBackend::CYK,
+            ParserBackend::GLL,
+            ParserBackend::LR(LRKind::LR1),
+        ] {
+            let sppf = grammar.parser(backend).unwrap().parse(word).unwrap();
+            assert_eq!(sppf.tree().unwrap(), tree);
+        }
+    }
+}
+
+#[test]
+fn test_trees_ambiguous() {
+    let grammar = grammar! {
+        e = e "+" e | "1"
+    };
+    let catalan = [1u32, 1, 2, 5, 14, 42, 132];
+    for (operators, expected) in catalan.iter().enumerate() {
+        let word = vec!["1"; operators + 1].join("+");
+        let sppf = grammar.parse(word.as_str()).unwrap();
+        assert_eq!(sppf.count_trees(), Some((*expected).into()));
+        let trees = sppf.trees().collect::<Vec<ParseTree>>();
+        assert_eq!(trees.len(), *expected as usize);
+        assert!(trees.iter().all(|tree| leaves(tree) == word));
+        let unique = trees
+            .iter()
+            .map(|tree| tree.to_string())
+            .collect::<HashSet<String>>();
+        assert_eq!(unique.len(), trees.len());
+    }
+}
+
+#[test]
+fn test_trees_count_unambiguous() {
+    let grammar = corpus::grammar_int();
+    let sppf = grammar.parse("+9081726354").unwrap();
+    assert_eq!(sppf.count_trees(), Some(1u32.into()));
+    assert_eq!(sppf.trees().count(), 1);
+}
+
+#[test]
+fn test_trees_deep() {
+    let grammar = grammar! {
+        s = "a" s | ""
+    };
+    let tree = grammar
+        .parse("a".repeat(20000).as_str())
+        .unwrap()
+        .tree()
+        .unwrap();
+    let mut depth = 0;
+    let mut node = &tree;
+    while let Some(child) = node.children().last() {
+        depth += 1;
+        node = child;
+    }
+    assert_eq!(depth, 20001);
+}
+
+#[test]
+fn test_trees_cyclic() {
+    let grammar = grammar! {
+        s = s | "a"
+    };
+    let sppf = grammar.parse("a").unwrap();
+    assert_eq!(sppf.count_trees(), None);
+    let trees = sppf.trees().collect::<Vec<ParseTree>>();
+    assert_eq!(trees.len(), 1);
+    assert_eq!(trees[0].children().len(), 1);
+    assert!(trees[0].children()[0].term().is_terminal());
+}
@@ -0,0 +1,10 @@
+use bnf::*;
+
+fn main() {
+    let _ = grammar! {
+        list = item ("," item)*
+        item = letter | digits
+        letter = "a" | "b"
+        digit = "0" | "1"
+    };
+}
@@ -0,0 +1,10 @@
+use bnf::*;
+
+fn main() {
+    let _ = grammar! { a = "x" | };
+    let _ = grammar! { a = ("x" | | "y") };
+    let _ = grammar! { a = "x" [b] };
+    let _ = grammar! { a = b = "x" };
+    let _ = grammar! { a = () };
+    let _ = grammar! { "a" = "x" };
+}
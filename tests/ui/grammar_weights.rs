@@ -0,0 +1,10 @@
+use bnf::*;
+
+fn main() {
+    let _ = grammar! { a = "x" @ };
+    let _ = grammar! { a = @ 2 | "x" };
+    let _ = grammar! { a = "x" @ "y" };
+    let _ = grammar! { a = "x" @ 2 3 };
+    let _ = grammar! { a = ("x" @ 2 | "y") };
+    let _ = grammar! { a = "x" @ 1e999 };
+}